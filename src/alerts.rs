@@ -0,0 +1,377 @@
+use crate::config::AlertRule;
+use crate::messages::MessageSender;
+use crate::resolver::HostnameCache;
+use crate::stats::{Probe, ProbeKey, Stats, StatsMap};
+use crate::webhooks::WebhookSender;
+use chrono::{DateTime, Local, TimeDelta};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How often rules are evaluated
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a recovery stays on the banner
+const RECOVERY_DISPLAY: Duration = Duration::from_secs(10);
+
+/// Results a loss or latency window needs before it is judged, unless this
+/// session's history already spans the whole window
+const MIN_WINDOW_SAMPLES: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertKind {
+    Firing,
+    Resolved,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertKind::Firing => "firing",
+            AlertKind::Resolved => "resolved",
+        }
+    }
+}
+
+/// An alert starting or ending on one probe
+#[derive(Clone, Debug)]
+pub struct AlertEvent {
+    pub kind: AlertKind,
+    pub ip: IpAddr,
+    pub hostname: Option<String>,
+    pub probe: Probe,
+    pub rule: AlertRule,
+    /// What tripped the rule, e.g. "loss 35.0% over 60s"
+    pub message: String,
+    pub at: DateTime<Local>,
+    /// How long the alert was firing (recoveries only)
+    pub duration: Option<Duration>,
+}
+
+impl AlertEvent {
+    /// JSON form shared by webhooks and headless output
+    pub fn to_json(&self) -> Value {
+        json!({
+            "state": self.kind.as_str(),
+            "host": self.ip.to_string(),
+            "hostname": self.hostname,
+            "probe": self.probe.to_string(),
+            "rule": self.rule.to_string(),
+            "message": self.message,
+            "time": self.at.to_rfc3339(),
+            "duration_secs": self.duration.map(|d| d.as_secs()),
+        })
+    }
+
+    /// Name and value of each field, as passed to `--on-alert` hooks in
+    /// `PINGER_ALERT_<NAME>` and to webhook templates as `{name}`
+    pub fn fields(&self) -> [(&'static str, String); 8] {
+        [
+            ("STATE", self.kind.as_str().to_string()),
+            ("HOST", self.ip.to_string()),
+            ("HOSTNAME", self.hostname.clone().unwrap_or_default()),
+            ("PROBE", self.probe.to_string()),
+            ("RULE", self.rule.to_string()),
+            ("MESSAGE", self.message.clone()),
+            ("TIME", self.at.to_rfc3339()),
+            ("DURATION", self.duration.map(|d| d.as_secs().to_string()).unwrap_or_default()),
+        ]
+    }
+}
+
+/// A rule currently tripped on a probe
+#[derive(Clone, Debug)]
+pub struct ActiveAlert {
+    pub ip: IpAddr,
+    pub probe: Probe,
+    pub rule: AlertRule,
+    pub message: String,
+    pub since: DateTime<Local>,
+}
+
+/// Alert rules and the alerts they currently raise
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    on_alert: Option<String>,
+    webhooks: WebhookSender,
+    /// Where failing `--on-alert` hooks are reported
+    messages: MessageSender,
+    /// Ring the terminal bell on new alerts
+    bell: bool,
+    /// Keyed by probe and rule index, so each rule fires at most once per probe
+    active: BTreeMap<(IpAddr, Probe, usize), ActiveAlert>,
+    recoveries: VecDeque<(Instant, AlertEvent)>,
+    last_check: Option<Instant>,
+}
+
+impl Alerts {
+    pub fn new(
+        rules: Vec<AlertRule>,
+        on_alert: Option<String>,
+        webhooks: WebhookSender,
+        messages: MessageSender,
+    ) -> Self {
+        Self {
+            rules,
+            on_alert,
+            webhooks,
+            messages,
+            bell: true,
+            active: BTreeMap::new(),
+            recoveries: VecDeque::new(),
+            last_check: None,
+        }
+    }
+
+    /// Turn the terminal bell off, e.g. when stdout carries machine-readable output
+    pub fn disable_bell(&mut self) {
+        self.bell = false;
+    }
+
+    /// Evaluate every rule against the running probes, at most once per `CHECK_INTERVAL`.
+    ///
+    /// Alerts on probes that are no longer running are dropped without a recovery.
+    pub fn check(
+        &mut self,
+        running: &[ProbeKey],
+        stats: &StatsMap,
+        hostnames: &HostnameCache,
+    ) -> Vec<AlertEvent> {
+        if self.rules.is_empty() || self.last_check.is_some_and(|t| t.elapsed() < CHECK_INTERVAL) {
+            return Vec::new();
+        }
+        self.last_check = Some(Instant::now());
+        self.recoveries.retain(|(at, _)| at.elapsed() < RECOVERY_DISPLAY);
+        self.active.retain(|(ip, probe, _), _| running.contains(&(*ip, *probe)));
+
+        let now = Local::now();
+        let mut events = Vec::new();
+        let stats_lock = stats.read();
+        for &(ip, probe) in running {
+            let Some(stats) = stats_lock.get(&(ip, probe)) else {
+                continue;
+            };
+
+            for (index, rule) in self.rules.iter().enumerate() {
+                let key = (ip, probe, index);
+                let hostname = || hostnames.read().get(&ip).cloned().flatten();
+
+                match (evaluate(rule, stats, now), self.active.get(&key)) {
+                    (Some(message), None) => {
+                        self.active.insert(
+                            key,
+                            ActiveAlert {
+                                ip,
+                                probe,
+                                rule: rule.clone(),
+                                message: message.clone(),
+                                since: now,
+                            },
+                        );
+                        events.push(AlertEvent {
+                            kind: AlertKind::Firing,
+                            ip,
+                            hostname: hostname(),
+                            probe,
+                            rule: rule.clone(),
+                            message,
+                            at: now,
+                            duration: None,
+                        });
+                    }
+                    // Still firing: keep the message current
+                    (Some(message), Some(_)) => {
+                        if let Some(alert) = self.active.get_mut(&key) {
+                            alert.message = message;
+                        }
+                    }
+                    (None, Some(_)) => {
+                        let Some(alert) = self.active.remove(&key) else {
+                            continue;
+                        };
+                        let event = AlertEvent {
+                            kind: AlertKind::Resolved,
+                            ip,
+                            hostname: hostname(),
+                            probe,
+                            rule: rule.clone(),
+                            message: format!("recovered from {}", alert.message),
+                            at: now,
+                            duration: (now - alert.since).to_std().ok(),
+                        };
+                        self.recoveries.push_back((Instant::now(), event.clone()));
+                        events.push(event);
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Alerts currently firing, ordered by host and probe
+    pub fn active(&self) -> impl Iterator<Item = &ActiveAlert> {
+        self.active.values()
+    }
+
+    /// Recoveries from the last few seconds, newest last
+    pub fn recent_recoveries(&self) -> impl Iterator<Item = &AlertEvent> {
+        self.recoveries.iter().map(|(_, event)| event)
+    }
+
+    /// Ring the terminal bell for new alerts, run the `--on-alert` hook and queue webhook deliveries
+    pub fn notify(&self, event: &AlertEvent) {
+        if self.bell && event.kind == AlertKind::Firing {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
+
+        if let Some(command) = &self.on_alert {
+            run_hook(command, event, &self.messages);
+        }
+        self.webhooks.send(event);
+    }
+}
+
+/// Why a rule holds for a probe right now, or `None` if it doesn't.
+///
+/// Only results of this session count; history restored from the database is
+/// for display and must not raise alerts on startup.
+fn evaluate(rule: &AlertRule, stats: &Stats, now: DateTime<Local>) -> Option<String> {
+    let timeline = stats.timeline();
+    let live_since = timeline.live_since()?;
+    // Summary of the window, if it holds enough results to judge
+    let judged = |window: &Duration| {
+        let start = now - TimeDelta::from_std(*window).ok()?;
+        let summary = timeline.summarize(start.max(live_since));
+        (summary.samples >= MIN_WINDOW_SAMPLES || live_since <= start).then_some(summary)
+    };
+    match rule {
+        AlertRule::Unreachable(count) => {
+            let failed = timeline.trailing_lost_live();
+            (failed >= *count as usize).then(|| format!("{} failed probes in a row", failed))
+        }
+        AlertRule::Loss { percent, window } => {
+            let summary = judged(window)?;
+            let loss = summary.loss_percent();
+            (summary.samples > 0 && loss > *percent)
+                .then(|| format!("loss {:.1}% over {}s", loss, window.as_secs()))
+        }
+        AlertRule::Latency { threshold, window } => {
+            let avg = judged(window)?.avg()?;
+            (avg > *threshold).then(|| {
+                format!(
+                    "avg latency {:.1}ms over {}s",
+                    avg.as_secs_f64() * 1000.0,
+                    window.as_secs()
+                )
+            })
+        }
+        AlertRule::Http5xx => match stats {
+            Stats::Http(http) => http
+                .last_status_code
+                .filter(|code| (500..=599).contains(code))
+                .map(|code| format!("HTTP {}", code)),
+            _ => None,
+        },
+    }
+}
+
+/// Run the hook through the shell with the alert in `PINGER_ALERT_*` variables
+fn run_hook(command: &str, event: &AlertEvent, messages: &MessageSender) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(
+            event
+                .fields()
+                .into_iter()
+                .map(|(name, value)| (format!("PINGER_ALERT_{}", name), value)),
+        )
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    // Reap the hook in the background; a failing hook must not disturb monitoring
+    match child {
+        Ok(mut child) => {
+            let messages = messages.clone();
+            tokio::spawn(async move {
+                match child.wait().await {
+                    Ok(status) if !status.success() => {
+                        messages.warning(format!("Alert hook exited with {}", status));
+                    }
+                    Ok(_) => {}
+                    Err(e) => messages.warning(format!("Failed to wait for alert hook: {}", e)),
+                }
+            });
+        }
+        Err(e) => messages.error(format!("Failed to run alert hook: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::ResultDetail;
+    use crate::stats::PingStats;
+
+    const LOSS: AlertRule = AlertRule::Loss {
+        percent: 20.0,
+        window: Duration::from_secs(60),
+    };
+    const LATENCY: AlertRule = AlertRule::Latency {
+        threshold: Duration::from_millis(100),
+        window: Duration::from_secs(60),
+    };
+
+    #[test]
+    fn windows_need_enough_results() {
+        let mut ping = PingStats::new();
+        ping.update(None);
+        let now = Local::now();
+        assert_eq!(evaluate(&LOSS, &Stats::Ping(ping.clone()), now), None);
+
+        for _ in 0..MIN_WINDOW_SAMPLES - 1 {
+            ping.update(None);
+        }
+        let message = evaluate(&LOSS, &Stats::Ping(ping), Local::now());
+        assert_eq!(message.as_deref(), Some("loss 100.0% over 60s"));
+
+        let mut slow = PingStats::new();
+        slow.update(Some(Duration::from_millis(500)));
+        assert_eq!(evaluate(&LATENCY, &Stats::Ping(slow.clone()), Local::now()), None);
+
+        for _ in 0..MIN_WINDOW_SAMPLES - 1 {
+            slow.update(Some(Duration::from_millis(500)));
+        }
+        let message = evaluate(&LATENCY, &Stats::Ping(slow), Local::now());
+        assert_eq!(message.as_deref(), Some("avg latency 500.0ms over 60s"));
+    }
+
+    #[test]
+    fn restored_results_do_not_fire() {
+        let mut stats = Stats::Ping(PingStats::new());
+        let now = Local::now();
+        for i in 0..10 {
+            stats.restore(now - TimeDelta::seconds(10 - i), None, ResultDetail::default());
+        }
+        assert_eq!(evaluate(&AlertRule::Unreachable(3), &stats, now), None);
+        assert_eq!(evaluate(&LOSS, &stats, now), None);
+
+        if let Stats::Ping(ping) = &mut stats {
+            for _ in 0..3 {
+                ping.update(None);
+            }
+        }
+        let message = evaluate(&AlertRule::Unreachable(3), &stats, Local::now());
+        assert_eq!(message.as_deref(), Some("3 failed probes in a row"));
+    }
+}
@@ -0,0 +1,548 @@
+use crate::app::App;
+use crate::config::{ApiListen, ExportFormat, ProbeSpec};
+use crate::export;
+use crate::parser::ScannedHost;
+use anyhow::{anyhow, Context, Result};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{CONTENT_TYPE, HOST};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::sync::{mpsc, oneshot};
+
+/// Largest request body accepted
+const MAX_BODY: usize = 64 * 1024;
+
+/// How long a request waits for the app loop to pick it up
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause after a failed accept, since errors like running out of file descriptors persist
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Something the API asks the running app to do
+#[derive(Debug)]
+pub enum ApiCommand {
+    Status,
+    ListHosts,
+    GetHost(IpAddr),
+    SetMonitoring(IpAddr, bool),
+    AddHost {
+        scan: ScannedHost,
+        probes: Option<Vec<ProbeSpec>>,
+        start: bool,
+    },
+    SetPaused(bool),
+    /// Formats to write, or the configured ones
+    Export(Option<Vec<ExportFormat>>),
+}
+
+/// A command plus where to send its result
+pub struct ApiRequest {
+    pub command: ApiCommand,
+    pub reply: oneshot::Sender<ApiResponse>,
+}
+
+pub type ApiResponse = (StatusCode, Value);
+
+/// A running API server: the requests it has received, and the Unix socket
+/// to remove when it is dropped
+pub struct ApiServer {
+    requests: mpsc::UnboundedReceiver<ApiRequest>,
+    socket: Option<PathBuf>,
+}
+
+impl ApiServer {
+    /// The next request waiting for the app, if any
+    pub fn try_recv(&mut self) -> Option<ApiRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Start serving the control API; the app takes requests from the returned
+/// server on each tick (see `App::attach_api`).
+///
+/// Binding happens before returning so a taken address fails at startup.
+pub async fn spawn(listen: &ApiListen) -> Result<ApiServer> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut socket = None;
+    match listen {
+        ApiListen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .context(format!("Failed to listen for the API on {}", addr))?;
+            serve_tcp(listener, tx);
+        }
+        ApiListen::Unix(path) => {
+            // Replace a socket left behind by an earlier run, but never a regular file
+            if let Ok(meta) = std::fs::symlink_metadata(path) {
+                if meta.file_type().is_socket() {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+            let listener = UnixListener::bind(path)
+                .context(format!("Failed to listen for the API on {}", path.display()))?;
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(serve_connection(stream, tx.clone()));
+                        }
+                        Err(_) => tokio::time::sleep(ACCEPT_BACKOFF).await,
+                    }
+                }
+            });
+            socket = Some(path.clone());
+        }
+    }
+    Ok(ApiServer {
+        requests: rx,
+        socket,
+    })
+}
+
+fn serve_tcp(listener: TcpListener, requests: mpsc::UnboundedSender<ApiRequest>) {
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_connection(stream, requests.clone()));
+                }
+                Err(_) => tokio::time::sleep(ACCEPT_BACKOFF).await,
+            }
+        }
+    });
+}
+
+async fn serve_connection<S>(stream: S, requests: mpsc::UnboundedSender<ApiRequest>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        let requests = requests.clone();
+        async move {
+            let (status, body) = handle(request, &requests).await;
+            let mut response = Response::new(Full::new(Bytes::from(format!("{:#}\n", body))));
+            *response.status_mut() = status;
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, hyper::header::HeaderValue::from_static("application/json"));
+            Ok::<_, Infallible>(response)
+        }
+    });
+    let _ = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .await;
+}
+
+/// Route a request to a command and wait for the app to run it
+async fn handle(
+    request: Request<Incoming>,
+    requests: &mpsc::UnboundedSender<ApiRequest>,
+) -> ApiResponse {
+    let command = match route(request).await {
+        Ok(command) => command,
+        Err((status, message)) => return error(status, &message),
+    };
+
+    let (reply, response) = oneshot::channel();
+    if requests.send(ApiRequest { command, reply }).is_err() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "Shutting down");
+    }
+    match tokio::time::timeout(REPLY_TIMEOUT, response).await {
+        Ok(Ok(response)) => response,
+        _ => error(StatusCode::SERVICE_UNAVAILABLE, "The app did not answer in time"),
+    }
+}
+
+async fn route(request: Request<Incoming>) -> Result<ApiCommand, (StatusCode, String)> {
+    // A web page can send simple cross-origin POSTs to loopback, or rebind its
+    // own name to 127.0.0.1; neither gets past these two checks
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("");
+    if !is_loopback_host(host) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Host must be a loopback name or address".to_string(),
+        ));
+    }
+    if request.method() == Method::POST && !is_json(&request) {
+        return Err((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "POST requests must be Content-Type: application/json".to_string(),
+        ));
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+
+    let host_ip = |segment: &str| {
+        segment
+            .parse::<IpAddr>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid IP address: {}", segment)))
+    };
+
+    match (&method, segments.as_slice()) {
+        (&Method::GET, ["status"]) => Ok(ApiCommand::Status),
+        (&Method::GET, ["hosts"]) => Ok(ApiCommand::ListHosts),
+        (&Method::POST, ["hosts"]) => {
+            let body = read_json(request).await?;
+            parse_add_host(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))
+        }
+        (&Method::GET, ["hosts", ip]) => Ok(ApiCommand::GetHost(host_ip(ip)?)),
+        (&Method::POST, ["hosts", ip, "start"]) => Ok(ApiCommand::SetMonitoring(host_ip(ip)?, true)),
+        (&Method::POST, ["hosts", ip, "stop"]) => Ok(ApiCommand::SetMonitoring(host_ip(ip)?, false)),
+        (&Method::POST, ["pause"]) => Ok(ApiCommand::SetPaused(true)),
+        (&Method::POST, ["resume"]) => Ok(ApiCommand::SetPaused(false)),
+        (&Method::POST, ["export"]) => {
+            let body = read_json(request).await?;
+            parse_export(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))
+        }
+        (_, ["status" | "hosts" | "pause" | "resume" | "export"])
+        | (_, ["hosts", _])
+        | (_, ["hosts", _, "start" | "stop"]) => Err((
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("{} not allowed on {}", method, path),
+        )),
+        _ => Err((StatusCode::NOT_FOUND, format!("No such endpoint: {}", path))),
+    }
+}
+
+/// Whether a Host header names this machine: `localhost` or a loopback address, with an optional port
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => match host.rsplit_once(':') {
+            Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host,
+        },
+    };
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn is_json(request: &Request<Incoming>) -> bool {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+async fn read_json(request: Request<Incoming>) -> Result<Value, (StatusCode, String)> {
+    let bytes = Limited::new(request.into_body(), MAX_BODY)
+        .collect()
+        .await
+        .map_err(|_| (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large".to_string()))?
+        .to_bytes();
+    // Bodies are optional where every field is
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Value::Null);
+    }
+    serde_json::from_slice(&bytes)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)))
+}
+
+/// `{"ip": "10.0.0.9", "hostname": "db", "probes": ["icmp", "http:80"], "start": true}`;
+/// only `ip` is required
+fn parse_add_host(body: &Value) -> Result<ApiCommand> {
+    let ip: IpAddr = body["ip"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Missing \"ip\""))?
+        .parse()
+        .context("Invalid \"ip\"")?;
+
+    let mut scan = ScannedHost::new(ip);
+    if let Some(name) = body["hostname"].as_str() {
+        scan.hostnames.push(name.to_string());
+    }
+
+    let probes = match &body["probes"] {
+        Value::Null => None,
+        Value::Array(specs) => Some(
+            specs
+                .iter()
+                .map(|spec| {
+                    spec.as_str()
+                        .ok_or_else(|| anyhow!("Probes must be strings such as \"icmp\" or \"http:80\""))
+                        .and_then(ProbeSpec::parse)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        _ => return Err(anyhow!("\"probes\" must be a list")),
+    };
+
+    Ok(ApiCommand::AddHost {
+        scan,
+        probes,
+        start: body["start"].as_bool().unwrap_or(true),
+    })
+}
+
+/// `{"formats": ["html", "json"]}`; an empty body uses the configured formats
+fn parse_export(body: &Value) -> Result<ApiCommand> {
+    let formats = match &body["formats"] {
+        Value::Null => None,
+        Value::Array(formats) => Some(
+            formats
+                .iter()
+                .map(|format| {
+                    format
+                        .as_str()
+                        .ok_or_else(|| anyhow!("Formats must be strings such as \"csv\" or \"html\""))
+                        .and_then(ExportFormat::parse)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        _ => return Err(anyhow!("\"formats\" must be a list")),
+    };
+    if formats.as_ref().is_some_and(|f| f.is_empty()) {
+        return Err(anyhow!("\"formats\" must not be empty"));
+    }
+    Ok(ApiCommand::Export(formats))
+}
+
+/// Run a command against the app; called from `App::tick`
+pub fn execute(app: &mut App, command: ApiCommand) -> ApiResponse {
+    match command {
+        ApiCommand::Status => (StatusCode::OK, status_json(app)),
+        ApiCommand::ListHosts => {
+            let hosts: Vec<Value> = app.hosts.iter().map(|host| export::host_json(app, host)).collect();
+            (StatusCode::OK, json!(hosts))
+        }
+        ApiCommand::GetHost(ip) => match app.hosts.iter().find(|h| h.ip == ip) {
+            Some(host) => (StatusCode::OK, export::host_json(app, host)),
+            None => unknown_host(ip),
+        },
+        ApiCommand::SetMonitoring(ip, selected) => {
+            if !app.set_monitoring(ip, selected) {
+                return unknown_host(ip);
+            }
+            match app.hosts.iter().find(|h| h.ip == ip) {
+                Some(host) => (StatusCode::OK, export::host_json(app, host)),
+                None => unknown_host(ip),
+            }
+        }
+        ApiCommand::AddHost { scan, probes, start } => {
+            let ip = scan.ip;
+            if app.hosts.iter().any(|h| h.ip == ip) {
+                return error(StatusCode::CONFLICT, &format!("Host {} is already monitored", ip));
+            }
+            if let Err(e) = app.add_host(scan, probes) {
+                return error(StatusCode::BAD_REQUEST, &format!("{:#}", e));
+            }
+            if start {
+                app.set_monitoring(ip, true);
+            }
+            match app.hosts.iter().find(|h| h.ip == ip) {
+                Some(host) => (StatusCode::CREATED, export::host_json(app, host)),
+                None => unknown_host(ip),
+            }
+        }
+        ApiCommand::SetPaused(paused) => {
+            app.set_paused(paused);
+            (StatusCode::OK, status_json(app))
+        }
+        ApiCommand::Export(formats) => match app.export_stats(formats.as_deref()) {
+            Ok(files) => (StatusCode::OK, json!({ "files": files })),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &format!("Export failed: {:#}", e)),
+        },
+    }
+}
+
+fn status_json(app: &App) -> Value {
+    json!({
+        "paused": app.paused,
+        "hosts": app.hosts.len(),
+        "selected": app.hosts.iter().filter(|h| h.selected).count(),
+        "running_probes": app.running_probes().len(),
+    })
+}
+
+fn unknown_host(ip: IpAddr) -> ApiResponse {
+    error(StatusCode::NOT_FOUND, &format!("Unknown host: {}", ip))
+}
+
+fn error(status: StatusCode, message: &str) -> ApiResponse {
+    (status, json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ExportSettings, HttpScheme, ProbeOverrides, Settings};
+    use reqwest::header::HeaderValue;
+    use std::collections::HashMap;
+
+    fn app(export_dir: PathBuf) -> App {
+        let settings = Settings {
+            probes: vec![ProbeSpec::Tcp(Some(9))],
+            http_scheme: HttpScheme::Http,
+            host_probes: HashMap::new(),
+            ipv6_scope_id: None,
+            resolve_dns: false,
+            probe_overrides: ProbeOverrides::default(),
+            host_overrides: HashMap::new(),
+            alert_rules: Vec::new(),
+            on_alert: None,
+            webhooks: Vec::new(),
+            webhook_rate: 20,
+            export: ExportSettings {
+                dir: export_dir,
+                ..Default::default()
+            },
+        };
+        App::new(vec![ScannedHost::new("127.0.0.1".parse().unwrap())], settings)
+    }
+
+    /// Send a request, with a JSON content type unless `content_type` says otherwise
+    async fn send(
+        client: &reqwest::Client,
+        base: &str,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+        content_type: &str,
+    ) -> (StatusCode, Value) {
+        let mut request = client.request(method, format!("{}{}", base, path));
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+        let response = request
+            .header(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap())
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, response.json().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn serves_every_endpoint_over_loopback() {
+        let export_dir = std::env::temp_dir().join(format!("pinger-api-test-{}", std::process::id()));
+        let mut app = app(export_dir.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        serve_tcp(listener, tx);
+        app.attach_api(ApiServer {
+            requests: rx,
+            socket: None,
+        });
+
+        let client_task = tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let json = "application/json";
+            let get = |path: &'static str| send(&client, &base, Method::GET, path, None, json);
+            let mut results = Vec::new();
+
+            results.push(("status", get("/status").await));
+            results.push(("hosts", get("/hosts").await));
+            results.push(("host", get("/hosts/127.0.0.1").await));
+            results.push(("unknown host", get("/hosts/10.9.9.9").await));
+            results.push(("bad ip", get("/hosts/nope").await));
+            results.push(("no endpoint", get("/nope").await));
+            results.push(("wrong method", send(&client, &base, Method::DELETE, "/status", None, json).await));
+
+            let add = r#"{"ip": "127.0.0.2", "hostname": "two", "probes": ["tcp:9"], "start": false}"#;
+            results.push(("add", send(&client, &base, Method::POST, "/hosts", Some(add), json).await));
+            results.push(("add again", send(&client, &base, Method::POST, "/hosts", Some(add), json).await));
+            let no_probes = r#"{"ip": "127.0.0.3", "probes": []}"#;
+            results.push(("no probes", send(&client, &base, Method::POST, "/hosts", Some(no_probes), json).await));
+            let plain = r#"{"ip": "127.0.0.4"}"#;
+            results.push(("text/plain", send(&client, &base, Method::POST, "/hosts", Some(plain), "text/plain").await));
+            results.push(("start", send(&client, &base, Method::POST, "/hosts/127.0.0.2/start", None, json).await));
+            results.push(("stop", send(&client, &base, Method::POST, "/hosts/127.0.0.2/stop", None, json).await));
+            results.push(("pause", send(&client, &base, Method::POST, "/pause", None, json).await));
+            results.push(("resume", send(&client, &base, Method::POST, "/resume", None, json).await));
+            let formats = r#"{"formats": ["json"]}"#;
+            results.push(("export", send(&client, &base, Method::POST, "/export", Some(formats), json).await));
+            let unknown_format = r#"{"formats": ["pdf"]}"#;
+            results.push(("bad format", send(&client, &base, Method::POST, "/export", Some(unknown_format), json).await));
+
+            let rebound = client
+                .get(format!("{}/status", base))
+                .header(HOST, "attacker.example")
+                .send()
+                .await
+                .unwrap();
+            results.push(("foreign host", (rebound.status(), rebound.json().await.unwrap())));
+            results
+        });
+
+        // Requests are only answered while the app ticks, as in the real event loop
+        while !client_task.is_finished() {
+            app.tick();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let results: HashMap<&str, (StatusCode, Value)> = client_task.await.unwrap().into_iter().collect();
+        let check = |name: &str, status: StatusCode| {
+            let (actual, body) = &results[name];
+            assert_eq!(*actual, status, "{}: {}", name, body);
+            body.clone()
+        };
+
+        let status = check("status", StatusCode::OK);
+        assert_eq!(status["hosts"], 1);
+        assert_eq!(status["paused"], false);
+        assert_eq!(check("hosts", StatusCode::OK)[0]["ip"], "127.0.0.1");
+        assert_eq!(check("host", StatusCode::OK)["probes"][0]["probe"], "TCP:9");
+        check("unknown host", StatusCode::NOT_FOUND);
+        check("bad ip", StatusCode::BAD_REQUEST);
+        check("no endpoint", StatusCode::NOT_FOUND);
+        check("wrong method", StatusCode::METHOD_NOT_ALLOWED);
+
+        let added = check("add", StatusCode::CREATED);
+        assert_eq!(added["hostname"], "two");
+        assert_eq!(added["selected"], false);
+        check("add again", StatusCode::CONFLICT);
+        check("no probes", StatusCode::BAD_REQUEST);
+        check("text/plain", StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(check("start", StatusCode::OK)["selected"], true);
+        assert_eq!(check("stop", StatusCode::OK)["selected"], false);
+        assert_eq!(check("pause", StatusCode::OK)["paused"], true);
+        assert_eq!(check("resume", StatusCode::OK)["paused"], false);
+        check("bad format", StatusCode::BAD_REQUEST);
+        check("foreign host", StatusCode::FORBIDDEN);
+
+        let files = check("export", StatusCode::OK)["files"].clone();
+        let file = files[0].as_str().unwrap();
+        assert!(file.ends_with(".json"), "{}", file);
+        assert!(std::path::Path::new(file).exists());
+
+        // Neither rejected POST reached the app
+        assert_eq!(app.hosts.len(), 2);
+        let _ = std::fs::remove_dir_all(export_dir);
+    }
+
+    #[test]
+    fn accepts_only_loopback_host_headers() {
+        for host in ["localhost", "LOCALHOST:9185", "127.0.0.1", "127.0.0.1:9185", "[::1]", "[::1]:9185"] {
+            assert!(is_loopback_host(host), "{}", host);
+        }
+        for host in ["", "example.com", "example.com:9185", "10.0.0.1:9185", "localhost.example.com", "[::2]:80"] {
+            assert!(!is_loopback_host(host), "{}", host);
+        }
+    }
+}
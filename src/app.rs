@@ -1,74 +1,267 @@
+use crate::alerts::{AlertEvent, Alerts};
+use crate::api::{self, ApiServer};
+use crate::config::{
+    ExportFormat, ExportSettings, HttpScheme, ProbeConfig, ProbeOverrides, ProbeSpec, Settings,
+};
+use crate::db::Database;
+use crate::export;
+use crate::history::StatusChange;
 use crate::http_checker;
-use crate::http_stats::HttpStats;
-use crate::pinger;
-use crate::stats::{AppMode, PingStats, Stats};
-use anyhow::Result;
-use chrono::Local;
+use crate::messages::{Level, Messages};
+use crate::parser::ScannedHost;
+use crate::pinger::{self, IcmpClients};
+use crate::resolver::{self, HostnameCache};
+use crate::stats::{AppMode, Probe, ProbeKey, StatsMap};
+use crate::tcp_checker;
+use crate::webhooks::WebhookSender;
+use anyhow::{anyhow, Result};
+use chrono::TimeDelta;
 use crossterm::event::{KeyCode, KeyEvent};
 use parking_lot::RwLock;
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
 use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::watch;
 
+/// Events scrolled per PageUp/PageDown
+const EVENTS_PAGE: usize = 5;
+
+/// Time span shown by the host detail chart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChartWindow {
+    OneMinute,
+    TenMinutes,
+    OneHour,
+    OneDay,
+}
+
+impl ChartWindow {
+    pub fn duration(&self) -> TimeDelta {
+        match self {
+            ChartWindow::OneMinute => TimeDelta::minutes(1),
+            ChartWindow::TenMinutes => TimeDelta::minutes(10),
+            ChartWindow::OneHour => TimeDelta::hours(1),
+            ChartWindow::OneDay => TimeDelta::days(1),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartWindow::OneMinute => "1m",
+            ChartWindow::TenMinutes => "10m",
+            ChartWindow::OneHour => "1h",
+            ChartWindow::OneDay => "24h",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            ChartWindow::OneMinute => ChartWindow::TenMinutes,
+            ChartWindow::TenMinutes => ChartWindow::OneHour,
+            ChartWindow::OneHour => ChartWindow::OneDay,
+            ChartWindow::OneDay => ChartWindow::OneMinute,
+        }
+    }
+}
+
+/// A status change of one probe, tagged with where it happened
+#[derive(Clone, Debug)]
+pub struct HostEvent {
+    pub ip: IpAddr,
+    pub probe: Probe,
+    pub change: StatusChange,
+}
+
 #[derive(Clone, Debug)]
 pub struct Host {
     pub ip: IpAddr,
     pub selected: bool,
+    /// Metadata from the nmap scan (state, addresses, hostnames, ports)
+    pub scan: ScannedHost,
+    /// Probes run while the host is selected
+    pub probes: Vec<Probe>,
 }
 
 pub struct App {
-    pub mode: AppMode,
-    pub port: u16,
+    /// Scope id used for link-local IPv6 targets
+    pub ipv6_scope_id: Option<u32>,
+    /// Probes given to hosts added at runtime without their own list
+    default_probes: Vec<ProbeSpec>,
+    http_scheme: HttpScheme,
+    resolve_dns: bool,
+    /// Probe settings applied to every host
+    probe_overrides: ProbeOverrides,
+    host_overrides: HashMap<IpAddr, ProbeOverrides>,
     pub hosts: Vec<Host>,
     pub selected_index: usize,
-    pub stats: Arc<RwLock<HashMap<IpAddr, Stats>>>,
+    pub stats: StatsMap,
+    pub hostnames: HostnameCache,
     pub should_quit: bool,
     pub paused: bool,
-    task_handles: HashMap<IpAddr, tokio::task::JoinHandle<()>>,
-    shutdown_senders: HashMap<IpAddr, watch::Sender<bool>>,
+    /// Whether the HTTP status-code breakdown popup is open
+    pub show_status_codes: bool,
+    /// Whether the HTTP panel shows per-phase timings instead of the overview
+    pub show_http_timings: bool,
+    /// Whether the ICMP and HTTP tables show percentile, std dev and jitter columns
+    pub show_latency_spread: bool,
+    /// Whether the detail screen for the highlighted host is open
+    pub show_detail: bool,
+    pub chart_window: ChartWindow,
+    /// Whether the event log pane is shown below the tables
+    pub show_events: bool,
+    /// How many of the newest events the event pane scrolls past
+    pub events_scroll: usize,
+    pub alerts: Alerts,
+    /// Toasts and the task error log
+    pub messages: Messages,
+    /// Whether the message log popup is open
+    pub show_log: bool,
+    /// Where and in which formats 's' writes the report
+    export: ExportSettings,
+    /// Requests from the control API, handled on each tick
+    api: Option<ApiServer>,
+    /// History persistence (`--db`)
+    database: Option<Database>,
+    icmp_clients: IcmpClients,
+    task_handles: HashMap<ProbeKey, tokio::task::JoinHandle<()>>,
+    shutdown_senders: HashMap<ProbeKey, watch::Sender<bool>>,
 }
 
 impl App {
-    pub fn new(ips: Vec<IpAddr>, mode: AppMode, port: u16) -> Self {
-        let hosts: Vec<Host> = ips
+    pub fn new(scanned: Vec<ScannedHost>, settings: Settings) -> Self {
+        let Settings {
+            probes,
+            http_scheme,
+            host_probes,
+            ipv6_scope_id,
+            resolve_dns,
+            probe_overrides,
+            host_overrides,
+            alert_rules,
+            on_alert,
+            webhooks,
+            webhook_rate,
+            export,
+        } = settings;
+
+        let hosts: Vec<Host> = scanned
             .into_iter()
-            .map(|ip| Host {
-                ip,
-                selected: false,
+            .map(|scan| {
+                let specs = host_probes.get(&scan.ip).unwrap_or(&probes);
+                let probes = expand_probes(specs, &scan, http_scheme);
+                Host {
+                    ip: scan.ip,
+                    selected: false,
+                    scan,
+                    probes,
+                }
             })
             .collect();
 
-        let stats_map = Arc::new(RwLock::new(HashMap::new()));
+        let stats_map: StatsMap = Arc::new(RwLock::new(HashMap::new()));
+        let messages = Messages::default();
 
-        // Initialize stats for all hosts based on mode
+        // Initialize stats for every probe of every host
         {
             let mut stats = stats_map.write();
             for host in &hosts {
-                let stat = match mode {
-                    AppMode::Icmp => Stats::Ping(PingStats::new()),
-                    AppMode::Http => Stats::Http(HttpStats::new()),
-                };
-                stats.insert(host.ip, stat);
+                for probe in &host.probes {
+                    stats.insert((host.ip, *probe), probe.new_stats());
+                }
             }
         }
 
+        // Seed hostnames from nmap, then reverse-resolve the rest in the background
+        let hostnames: HostnameCache = Arc::new(RwLock::new(HashMap::new()));
+        {
+            let mut cache = hostnames.write();
+            for host in &hosts {
+                if let Some(name) = host.scan.hostnames.first() {
+                    cache.insert(host.ip, Some(name.clone()));
+                }
+            }
+        }
+        if resolve_dns {
+            let unresolved: Vec<IpAddr> = hosts
+                .iter()
+                .filter(|h| h.scan.hostnames.is_empty())
+                .map(|h| h.ip)
+                .collect();
+            resolver::spawn_reverse_lookups(unresolved, Arc::clone(&hostnames));
+        }
+
         Self {
-            mode,
-            port,
+            ipv6_scope_id,
+            default_probes: probes,
+            http_scheme,
+            resolve_dns,
+            probe_overrides,
+            host_overrides,
             hosts,
             selected_index: 0,
             stats: stats_map,
+            hostnames,
             should_quit: false,
             paused: false,
+            show_status_codes: false,
+            show_http_timings: false,
+            show_latency_spread: false,
+            show_detail: false,
+            chart_window: ChartWindow::OneMinute,
+            show_events: true,
+            events_scroll: 0,
+            alerts: Alerts::new(
+                alert_rules,
+                on_alert,
+                WebhookSender::spawn(webhooks, webhook_rate, messages.sender()),
+                messages.sender(),
+            ),
+            messages,
+            show_log: false,
+            export,
+            api: None,
+            database: None,
+            icmp_clients: IcmpClients::default(),
             task_handles: HashMap::new(),
             shutdown_senders: HashMap::new(),
         }
     }
 
     pub fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        // The message log swallows keys until closed
+        if self.show_log {
+            match key.code {
+                KeyCode::Char('l') | KeyCode::Esc => self.show_log = false,
+                KeyCode::PageUp => self.messages.scroll = self.messages.scroll.saturating_add(EVENTS_PAGE),
+                KeyCode::PageDown => self.messages.scroll = self.messages.scroll.saturating_sub(EVENTS_PAGE),
+                KeyCode::Home => self.messages.scroll = 0,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        // The status-code popup swallows keys until closed
+        if self.show_status_codes {
+            if matches!(key.code, KeyCode::Char('c') | KeyCode::Esc) {
+                self.show_status_codes = false;
+            }
+            return Ok(());
+        }
+
+        // The detail screen keeps host navigation but takes over Esc
+        if self.show_detail {
+            match key.code {
+                KeyCode::Esc | KeyCode::Enter => self.show_detail = false,
+                KeyCode::Char('q') => self.should_quit = true,
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Char('w') => self.chart_window = self.chart_window.next(),
+                _ => {}
+            }
+            return Ok(());
+        }
+
         match key.code {
             KeyCode::Char('q') | KeyCode::Esc => {
                 self.should_quit = true;
@@ -82,6 +275,9 @@ impl App {
             KeyCode::Char(' ') => {
                 self.toggle_selection();
             }
+            KeyCode::Enter => {
+                self.show_detail = !self.hosts.is_empty();
+            }
             KeyCode::Char('p') => {
                 self.toggle_pause();
             }
@@ -91,42 +287,229 @@ impl App {
             KeyCode::Char('d') => {
                 self.deselect_all();
             }
-            KeyCode::Char('s') => {
-                self.export_stats()?;
+            KeyCode::Char('s') => match self.export_stats(None) {
+                Ok(files) => {
+                    let summary = match files.as_slice() {
+                        [file] => format!("Exported {}", file),
+                        _ => format!("Exported {} files to {}", files.len(), self.export.dir.display()),
+                    };
+                    self.messages.toast(Level::Info, summary);
+                    if files.len() > 1 {
+                        self.messages.log(Level::Info, format!("Export files: {}", files.join(", ")));
+                    }
+                }
+                Err(e) => self.messages.toast(Level::Error, format!("Export failed: {:#}", e)),
+            },
+            KeyCode::Char('c') => {
+                self.show_status_codes = true;
+            }
+            KeyCode::Char('t') => {
+                self.show_http_timings = !self.show_http_timings;
+            }
+            KeyCode::Char('x') => {
+                self.show_latency_spread = !self.show_latency_spread;
+            }
+            KeyCode::Char('e') => {
+                self.show_events = !self.show_events;
+            }
+            KeyCode::Char('l') => {
+                self.show_log = true;
+                self.messages.scroll = 0;
+                self.messages.mark_read();
+            }
+            KeyCode::PageUp => {
+                self.events_scroll = self.events_scroll.saturating_add(EVENTS_PAGE);
+            }
+            KeyCode::PageDown => {
+                self.events_scroll = self.events_scroll.saturating_sub(EVENTS_PAGE);
+            }
+            KeyCode::Home => {
+                self.events_scroll = 0;
             }
             _ => {}
         }
         Ok(())
     }
 
-    fn move_selection(&mut self, delta: isize) {
-        if self.hosts.is_empty() {
-            return;
+    /// Periodic work between frames: collect task messages, answer control API requests,
+    /// queue new history for the database, evaluate alert rules and send notifications.
+    ///
+    /// Returns the alerts that fired or recovered on this tick.
+    pub fn tick(&mut self) -> Vec<AlertEvent> {
+        self.messages.receive();
+        while let Some(request) = self.api.as_mut().and_then(ApiServer::try_recv) {
+            let response = api::execute(self, request.command);
+            let _ = request.reply.send(response);
+        }
+        if let Some(database) = &mut self.database {
+            database.record(&self.stats);
         }
 
-        let len = self.hosts.len() as isize;
-        let new_index = (self.selected_index as isize + delta + len) % len;
-        self.selected_index = new_index as usize;
+        let events = self.alerts.check(&self.running_probes(), &self.stats, &self.hostnames);
+        for event in &events {
+            self.alerts.notify(event);
+        }
+        events
     }
 
-    fn toggle_selection(&mut self) {
-        if self.hosts.is_empty() {
-            return;
+    /// Probes with a task currently running
+    pub fn running_probes(&self) -> Vec<ProbeKey> {
+        self.task_handles.keys().copied().collect()
+    }
+
+    /// Answer the control API's requests on every tick
+    pub fn attach_api(&mut self, api: ApiServer) {
+        self.api = Some(api);
+    }
+
+    /// Persist history to `database` from now on
+    pub fn attach_database(&mut self, database: Database) {
+        self.database = Some(database);
+    }
+
+    /// Add a host that wasn't in the scan, with its own probes or the defaults
+    pub fn add_host(&mut self, scan: ScannedHost, specs: Option<Vec<ProbeSpec>>) -> Result<()> {
+        if self.hosts.iter().any(|h| h.ip == scan.ip) {
+            return Err(anyhow!("Host {} is already monitored", scan.ip));
+        }
+        let probes = expand_probes(specs.as_ref().unwrap_or(&self.default_probes), &scan, self.http_scheme);
+        if probes.is_empty() {
+            return Err(anyhow!("No probes for host {}", scan.ip));
         }
 
-        let ip = self.hosts[self.selected_index].ip;
-        let selected = self.hosts[self.selected_index].selected;
+        {
+            let mut stats = self.stats.write();
+            for probe in &probes {
+                stats.insert((scan.ip, *probe), probe.new_stats());
+            }
+        }
+        match scan.hostnames.first() {
+            Some(name) => {
+                self.hostnames.write().insert(scan.ip, Some(name.clone()));
+            }
+            None if self.resolve_dns => {
+                resolver::spawn_reverse_lookups(vec![scan.ip], Arc::clone(&self.hostnames));
+            }
+            None => {}
+        }
 
-        self.hosts[self.selected_index].selected = !selected;
+        self.hosts.push(Host {
+            ip: scan.ip,
+            selected: false,
+            scan,
+            probes,
+        });
+        Ok(())
+    }
+
+    /// Start or stop monitoring one host; tasks only run while not paused.
+    /// Returns false if the host is unknown.
+    pub fn set_monitoring(&mut self, ip: IpAddr, selected: bool) -> bool {
+        let Some(host) = self.hosts.iter_mut().find(|h| h.ip == ip) else {
+            return false;
+        };
+        if host.selected == selected {
+            return true;
+        }
+        host.selected = selected;
 
         // Only start/stop tasks if not paused
         if !self.paused {
-            if !selected {
+            if selected {
                 self.start_task(ip);
             } else {
                 self.stop_task(ip);
             }
         }
+        true
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused != paused {
+            self.toggle_pause();
+        }
+    }
+
+    /// Hostname for an IP, from nmap or reverse DNS, if one is known yet
+    pub fn hostname(&self, ip: &IpAddr) -> Option<String> {
+        self.hostnames.read().get(ip).cloned().flatten()
+    }
+
+    /// Effective settings for one probe of a host, with any per-host override applied
+    pub fn probe_config_for(&self, ip: &IpAddr, probe: Probe) -> ProbeConfig {
+        let config = ProbeConfig::defaults(probe.mode()).with(&self.probe_overrides);
+        match self.host_overrides.get(ip) {
+            Some(overrides) => config.with(overrides),
+            None => config,
+        }
+    }
+
+    /// Probe kinds configured on any host, in display order
+    pub fn active_modes(&self) -> Vec<AppMode> {
+        let mut modes: Vec<AppMode> = self
+            .hosts
+            .iter()
+            .flat_map(|h| h.probes.iter().map(|p| p.mode()))
+            .collect();
+        modes.sort();
+        modes.dedup();
+        modes
+    }
+
+    /// Every (host, probe) pair of the given kind
+    pub fn host_probes(&self, mode: AppMode) -> impl Iterator<Item = (&Host, Probe)> {
+        self.hosts.iter().flat_map(move |h| {
+            h.probes
+                .iter()
+                .filter(move |p| p.mode() == mode)
+                .map(move |p| (h, *p))
+        })
+    }
+
+    /// Every (host, probe) pair of the given kind on selected hosts
+    pub fn selected_probes(&self, mode: AppMode) -> impl Iterator<Item = (&Host, Probe)> {
+        self.host_probes(mode).filter(|(h, _)| h.selected)
+    }
+
+    /// Status changes of every probe, newest first
+    pub fn events(&self) -> Vec<HostEvent> {
+        let stats_lock = self.stats.read();
+        let mut events: Vec<HostEvent> = stats_lock
+            .iter()
+            .flat_map(|((ip, probe), stats)| {
+                stats.status_log().iter().map(|change| HostEvent {
+                    ip: *ip,
+                    probe: *probe,
+                    change: change.clone(),
+                })
+            })
+            .collect();
+        events.sort_by_key(|e| Reverse(e.change.at));
+        events
+    }
+
+    /// Whether a host has its own probe settings
+    pub fn has_override(&self, ip: &IpAddr) -> bool {
+        self.host_overrides.contains_key(ip)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.hosts.is_empty() {
+            return;
+        }
+
+        let len = self.hosts.len() as isize;
+        let new_index = (self.selected_index as isize + delta + len) % len;
+        self.selected_index = new_index as usize;
+    }
+
+    fn toggle_selection(&mut self) {
+        if self.hosts.is_empty() {
+            return;
+        }
+
+        let host = &self.hosts[self.selected_index];
+        self.set_monitoring(host.ip, !host.selected);
     }
 
     fn toggle_pause(&mut self) {
@@ -134,9 +517,9 @@ impl App {
 
         if self.paused {
             // Stop all running tasks
-            let ips: Vec<IpAddr> = self.task_handles.keys().copied().collect();
-            for ip in ips {
-                self.stop_task(ip);
+            let keys: Vec<ProbeKey> = self.task_handles.keys().copied().collect();
+            for key in keys {
+                self.stop_probe_task(key);
             }
         } else {
             // Restart tasks for all selected hosts
@@ -153,56 +536,109 @@ impl App {
         }
     }
 
+    /// Start every probe configured for a host
     fn start_task(&mut self, ip: IpAddr) {
+        let probes = match self.hosts.iter().find(|h| h.ip == ip) {
+            Some(host) => host.probes.clone(),
+            None => return,
+        };
+
+        for probe in probes {
+            self.start_probe_task((ip, probe));
+        }
+    }
+
+    fn start_probe_task(&mut self, key: ProbeKey) {
         // Don't start if already running
-        if self.task_handles.contains_key(&ip) {
+        if self.task_handles.contains_key(&key) {
             return;
         }
 
+        let (ip, probe) = key;
+
         // Create shutdown channel
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
         // Clone Arc for the async task
         let stats = Arc::clone(&self.stats);
-        let port = self.port;
-
-        // Spawn task based on mode
-        let handle = match self.mode {
-            AppMode::Icmp => tokio::spawn(async move {
-                pinger::start_ping_task(ip, stats, shutdown_rx).await;
-            }),
-            AppMode::Http => tokio::spawn(async move {
-                http_checker::start_http_task(ip, port, stats, shutdown_rx).await;
+        let scope_id = self.ipv6_scope_id;
+        let config = self.probe_config_for(&ip, probe);
+
+        // Spawn task based on probe kind
+        let handle = match probe {
+            Probe::Icmp => {
+                let client = match self.icmp_clients.client_for(ip, config.ttl) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.messages
+                            .log(Level::Error, format!("Failed to create ping client for {}: {}", ip, e));
+                        return;
+                    }
+                };
+                let ident = self.icmp_clients.next_identifier();
+                tokio::spawn(async move {
+                    pinger::start_ping_task(ip, client, ident, scope_id, config, stats, shutdown_rx)
+                        .await;
+                })
+            }
+            Probe::Http(_) | Probe::Https(_) => {
+                let messages = self.messages.sender();
+                tokio::spawn(async move {
+                    http_checker::start_http_task(ip, probe, config, stats, messages, shutdown_rx)
+                        .await;
+                })
+            }
+            Probe::Tcp(port) => tokio::spawn(async move {
+                tcp_checker::start_tcp_task(ip, port, config, stats, shutdown_rx).await;
             }),
         };
 
-        self.task_handles.insert(ip, handle);
-        self.shutdown_senders.insert(ip, shutdown_tx);
+        self.task_handles.insert(key, handle);
+        self.shutdown_senders.insert(key, shutdown_tx);
     }
 
+    /// Stop every probe running for a host
     fn stop_task(&mut self, ip: IpAddr) {
+        let keys: Vec<ProbeKey> = self
+            .task_handles
+            .keys()
+            .filter(|(task_ip, _)| *task_ip == ip)
+            .copied()
+            .collect();
+
+        for key in keys {
+            self.stop_probe_task(key);
+        }
+    }
+
+    fn stop_probe_task(&mut self, key: ProbeKey) {
         // Send shutdown signal
-        if let Some(sender) = self.shutdown_senders.remove(&ip) {
+        if let Some(sender) = self.shutdown_senders.remove(&key) {
             let _ = sender.send(true);
         }
 
         // Abort the task
-        if let Some(handle) = self.task_handles.remove(&ip) {
+        if let Some(handle) = self.task_handles.remove(&key) {
             handle.abort();
         }
     }
 
     fn select_all(&mut self) {
+        self.select_where(|_| true);
+    }
+
+    /// Select (and start monitoring) every host whose address passes `filter`
+    pub fn select_where(&mut self, filter: impl Fn(&IpAddr) -> bool) {
         if self.paused {
             // Just mark as selected, don't start tasks
-            for host in &mut self.hosts {
+            for host in self.hosts.iter_mut().filter(|h| filter(&h.ip)) {
                 host.selected = true;
             }
         } else {
             // Mark as selected and start tasks
             for i in 0..self.hosts.len() {
                 let ip = self.hosts[i].ip;
-                if !self.hosts[i].selected {
+                if filter(&ip) && !self.hosts[i].selected {
                     self.hosts[i].selected = true;
                     self.start_task(ip);
                 }
@@ -222,115 +658,41 @@ impl App {
         }
     }
 
-    fn export_stats(&self) -> Result<()> {
-        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-        let filename = format!("stats_export_{}.csv", timestamp);
-
-        let mut file = File::create(&filename)?;
-
-        // Write header based on mode
-        match self.mode {
-            AppMode::Icmp => {
-                writeln!(
-                    file,
-                    "IP,Status,Last Latency (ms),Avg Latency (ms),Min Latency (ms),Max Latency (ms),Packet Loss %,Packets Sent,Packets Received"
-                )?;
-
-                let stats_lock = self.stats.read();
-                for host in &self.hosts {
-                    if let Some(Stats::Ping(stats)) = stats_lock.get(&host.ip) {
-                        writeln!(
-                            file,
-                            "{},{:?},{},{},{},{},{:.2},{},{}",
-                            host.ip,
-                            stats.status,
-                            stats
-                                .last_latency
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .avg_latency
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .min_latency
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .max_latency
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats.packet_loss_percent,
-                            stats.packets_sent,
-                            stats.packets_received
-                        )?;
-                    }
-                }
-            }
-            AppMode::Http => {
-                writeln!(
-                    file,
-                    "IP,Status,Status Code,Last Response Time (ms),Avg Response Time (ms),Min Response Time (ms),Max Response Time (ms),Content Size,Success Rate %,Requests Sent,Requests Successful,Last Error"
-                )?;
-
-                let stats_lock = self.stats.read();
-                for host in &self.hosts {
-                    if let Some(Stats::Http(stats)) = stats_lock.get(&host.ip) {
-                        writeln!(
-                            file,
-                            "{},{:?},{},{},{},{},{},{},{:.2},{},{},{}",
-                            host.ip,
-                            stats.status,
-                            stats
-                                .last_status_code
-                                .map(|c| c.to_string())
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .last_response_time
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .avg_response_time
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .min_response_time
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .max_response_time
-                                .map(|d| format!("{:.2}", d.as_secs_f64() * 1000.0))
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats
-                                .last_content_size
-                                .map(|s| s.to_string())
-                                .unwrap_or_else(|| "-".to_string()),
-                            stats.success_rate_percent,
-                            stats.requests_sent,
-                            stats.requests_successful,
-                            stats
-                                .last_error
-                                .as_ref()
-                                .map(|e| format!("\"{}\"", e.replace('"', "'")))
-                                .unwrap_or_else(|| "-".to_string())
-                        )?;
-                    }
-                }
-            }
-        }
-
-        // Write success - we can't show a message in the TUI easily, but the file is created
-        Ok(())
+    /// Write the report in `formats`, or the configured ones, returning the paths written
+    pub fn export_stats(&self, formats: Option<&[ExportFormat]>) -> Result<Vec<String>> {
+        let formats = formats.unwrap_or(&self.export.formats);
+        let files = export::export(self, &self.export, formats)?;
+        Ok(files.iter().map(|path| path.display().to_string()).collect())
     }
 
     pub async fn shutdown(&mut self) {
+        // Stop answering the API, removing its Unix socket
+        self.api = None;
+
         // Stop all tasks
-        let ips: Vec<IpAddr> = self.task_handles.keys().copied().collect();
-        for ip in ips {
-            self.stop_task(ip);
+        let keys: Vec<ProbeKey> = self.task_handles.keys().copied().collect();
+        for key in keys {
+            self.stop_probe_task(key);
         }
 
         // Give tasks a moment to clean up
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        // Write the last results before the database closes
+        if let Some(mut database) = self.database.take() {
+            database.record(&self.stats);
+            database.close().await;
+        }
+    }
+}
+
+/// Bind probe specs to a host, using its nmap ports for port-less TCP probes and HTTPS detection
+fn expand_probes(specs: &[ProbeSpec], scan: &ScannedHost, scheme: HttpScheme) -> Vec<Probe> {
+    let mut probes: Vec<Probe> = Vec::new();
+    for probe in specs.iter().flat_map(|spec| spec.expand(scan, scheme)) {
+        if !probes.contains(&probe) {
+            probes.push(probe);
+        }
     }
+    probes
 }
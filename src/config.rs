@@ -0,0 +1,858 @@
+use crate::parser::ScannedHost;
+use crate::stats::{AppMode, Probe};
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Startup settings gathered from the command line
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Probes run against every host without its own list
+    pub probes: Vec<ProbeSpec>,
+    /// How `http` probes pick between HTTP and HTTPS
+    pub http_scheme: HttpScheme,
+    /// Per-host probe lists
+    pub host_probes: HashMap<IpAddr, Vec<ProbeSpec>>,
+    /// Scope id used for link-local IPv6 targets
+    pub ipv6_scope_id: Option<u32>,
+    pub resolve_dns: bool,
+    /// Probe settings applied to every host
+    pub probe_overrides: ProbeOverrides,
+    /// Per-host probe overrides
+    pub host_overrides: HashMap<IpAddr, ProbeOverrides>,
+    /// Conditions that raise an alert, checked against every running probe
+    pub alert_rules: Vec<AlertRule>,
+    /// Shell command run when an alert fires or recovers
+    pub on_alert: Option<String>,
+    /// URLs alert events are POSTed to
+    pub webhooks: Vec<Webhook>,
+    /// Most deliveries per webhook per minute; the rest are counted and reported later
+    pub webhook_rate: u32,
+    /// Where `s` and the API write exports, and in which formats
+    pub export: ExportSettings,
+}
+
+/// A file format statistics can be exported in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// One CSV file per probe kind, plus history and events
+    Csv,
+    Json,
+    Markdown,
+    /// Self-contained report with inline SVG charts
+    Html,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(anyhow!(
+                "Unknown export format: {} (expected csv, json, md or html)",
+                value
+            )),
+        }
+    }
+
+    /// Parse a comma-separated list such as `csv,html`
+    pub fn parse_list(value: &str) -> Result<Vec<Self>> {
+        let mut formats = Vec::new();
+        for format in value.split(',').filter(|f| !f.trim().is_empty()) {
+            let format = Self::parse(format)?;
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+        if formats.is_empty() {
+            return Err(anyhow!("No export format given"));
+        }
+        Ok(formats)
+    }
+}
+
+/// Output location and formats for exports
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    pub dir: PathBuf,
+    /// File name without extension; `{ts}` is replaced by the export time
+    pub name: String,
+    pub formats: Vec<ExportFormat>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            name: "stats_export_{ts}".to_string(),
+            formats: vec![ExportFormat::Csv],
+        }
+    }
+}
+
+/// Scheme selection for `http` probes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HttpScheme {
+    /// HTTPS for 443/8443 or ports nmap reported as ssl/http, HTTP otherwise
+    Auto,
+    Http,
+    Https,
+}
+
+impl HttpScheme {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Ok(HttpScheme::Auto),
+            "http" => Ok(HttpScheme::Http),
+            "https" => Ok(HttpScheme::Https),
+            _ => Err(anyhow!("Unknown scheme: {} (expected auto, http or https)", value)),
+        }
+    }
+}
+
+/// Request method used by HTTP probes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HttpMethod {
+    Get,
+    Head,
+    Post,
+}
+
+impl HttpMethod {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "GET" => Ok(HttpMethod::Get),
+            "HEAD" => Ok(HttpMethod::Head),
+            "POST" => Ok(HttpMethod::Post),
+            _ => Err(anyhow!("Unknown method: {} (expected GET, HEAD or POST)", value)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+        }
+    }
+}
+
+/// Expected content of an HTTP response body
+#[derive(Clone, Debug, PartialEq)]
+pub enum BodyMatch {
+    Contains(String),
+    /// Pattern is validated when parsed, so it always compiles
+    Regex(String),
+}
+
+/// Window used by loss and latency rules when none is given
+const DEFAULT_ALERT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A condition on a probe's recent results that raises an alert while it holds
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertRule {
+    /// This many failed probes in a row
+    Unreachable(u32),
+    /// Loss above a percentage over a trailing window
+    Loss { percent: f64, window: Duration },
+    /// Mean latency above a threshold over a trailing window
+    Latency { threshold: Duration, window: Duration },
+    /// The last HTTP response was a 5xx
+    Http5xx,
+}
+
+impl AlertRule {
+    /// Parse `unreachable=N`, `loss=PCT[%][/WINDOW]`, `latency=MS[ms][/WINDOW]` or `http-5xx`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (kind, value) = spec.split_once('=').unwrap_or((spec, ""));
+        let (value, window) = match value.split_once('/') {
+            Some((value, window)) => (value, parse_duration(window)?),
+            None => (value, DEFAULT_ALERT_WINDOW),
+        };
+
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "unreachable" => {
+                let count: u32 = value
+                    .parse()
+                    .context(format!("Invalid probe count: {}", value))?;
+                if count == 0 {
+                    return Err(anyhow!("Probe count must be greater than zero"));
+                }
+                Ok(AlertRule::Unreachable(count))
+            }
+            "loss" => {
+                let percent: f64 = value
+                    .trim_end_matches('%')
+                    .parse()
+                    .context(format!("Invalid loss percentage: {}", value))?;
+                if !(0.0..100.0).contains(&percent) {
+                    return Err(anyhow!("Loss percentage must be in 0..100: {}", value));
+                }
+                Ok(AlertRule::Loss { percent, window })
+            }
+            "latency" => Ok(AlertRule::Latency {
+                threshold: parse_millis(value.trim_end_matches("ms"))?,
+                window,
+            }),
+            "http-5xx" | "5xx" => Ok(AlertRule::Http5xx),
+            _ => Err(anyhow!(
+                "Unknown alert rule: {} (expected unreachable=N, loss=PCT[/WINDOW], latency=MS[/WINDOW] or http-5xx)",
+                spec
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertRule::Unreachable(count) => write!(f, "unreachable={}", count),
+            AlertRule::Loss { percent, window } => {
+                write!(f, "loss={}%/{}s", percent, window.as_secs())
+            }
+            AlertRule::Latency { threshold, window } => {
+                write!(f, "latency={}ms/{}s", threshold.as_millis(), window.as_secs())
+            }
+            AlertRule::Http5xx => write!(f, "http-5xx"),
+        }
+    }
+}
+
+/// Body layout of a webhook POST
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookFormat {
+    /// Flat JSON object with every alert field
+    Generic,
+    /// `{"text": ...}`, accepted by Slack incoming webhooks and compatible chat services
+    Slack,
+    /// User-supplied JSON with `{state}`, `{host}`, ... placeholders (`--webhook-template`)
+    Template(String),
+}
+
+/// Delay before a webhook's first retry
+const WEBHOOK_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Window `--webhook-rate` applies to
+const WEBHOOK_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Where alert events are POSTed, and in what format
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    pub url: String,
+    pub format: WebhookFormat,
+    /// Delay before the first retry; doubled after each failure
+    pub initial_backoff: Duration,
+    /// Window the per-webhook rate limit applies to
+    pub rate_window: Duration,
+}
+
+impl Webhook {
+    /// Parse `[slack:|generic:]URL`; plain URLs get the generic format
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (format, url) = match spec.split_once(':') {
+            Some(("slack", url)) => (WebhookFormat::Slack, url),
+            Some(("generic", url)) => (WebhookFormat::Generic, url),
+            _ => (WebhookFormat::Generic, spec),
+        };
+        let parsed = reqwest::Url::parse(url).context(format!("Invalid webhook URL: {}", url))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow!("Webhook URL must be http or https: {}", url));
+        }
+        Ok(Self {
+            url: url.to_string(),
+            format,
+            initial_backoff: WEBHOOK_BACKOFF,
+            rate_window: WEBHOOK_RATE_WINDOW,
+        })
+    }
+}
+
+/// Where the control API listens: a loopback TCP address or a Unix socket
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiListen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ApiListen {
+    /// Parse `HOST:PORT` (loopback only, since the API has no authentication) or `unix:PATH`
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some(path) = spec.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(anyhow!("Missing socket path in {}", spec));
+            }
+            return Ok(ApiListen::Unix(PathBuf::from(path)));
+        }
+        let addr: SocketAddr = spec
+            .parse()
+            .context(format!("Invalid API address: {} (expected HOST:PORT or unix:PATH)", spec))?;
+        if !addr.ip().is_loopback() {
+            return Err(anyhow!("The control API must listen on a loopback address, not {}", addr.ip()));
+        }
+        Ok(ApiListen::Tcp(addr))
+    }
+}
+
+impl fmt::Display for ApiListen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiListen::Tcp(addr) => write!(f, "{}", addr),
+            ApiListen::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// An address or network (`10.0.0.5`, `10.0.0.0/24`, `fd00::/64`) picking hosts to monitor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HostFilter {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl HostFilter {
+    /// Parse a comma-separated list of addresses and CIDR networks
+    pub fn parse_list(spec: &str) -> Result<Vec<Self>> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (addr, prefix) = spec.split_once('/').unwrap_or((spec, ""));
+        let network: IpAddr = addr.parse().context(format!("Invalid IP address: {}", addr))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = if prefix.is_empty() {
+            max
+        } else {
+            prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| anyhow!("Invalid prefix length in {}", spec))?
+        };
+        Ok(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A probe as written on the command line, before it is bound to a host
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbeSpec {
+    Icmp,
+    /// HTTP or HTTPS, chosen by the `HttpScheme` setting
+    Http(u16),
+    /// Always HTTPS
+    Https(u16),
+    /// TCP connect to a port, or to every open TCP port nmap reported when `None`
+    Tcp(Option<u16>),
+}
+
+impl ProbeSpec {
+    /// Parse `icmp`, `http[:PORT]`, `https[:PORT]` or `tcp[:PORT]`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (kind, port) = match spec.split_once(':') {
+            Some((kind, port)) => {
+                let port: u16 = port.parse().context(format!("Invalid port: {}", port))?;
+                (kind, Some(port))
+            }
+            None => (spec, None),
+        };
+
+        match (kind.to_ascii_lowercase().as_str(), port) {
+            ("icmp", None) => Ok(ProbeSpec::Icmp),
+            ("http", port) => Ok(ProbeSpec::Http(port.unwrap_or(80))),
+            ("https", port) => Ok(ProbeSpec::Https(port.unwrap_or(443))),
+            ("tcp", port) => Ok(ProbeSpec::Tcp(port)),
+            _ => Err(anyhow!(
+                "Unknown probe: {} (expected icmp, http[:PORT], https[:PORT] or tcp[:PORT])",
+                spec
+            )),
+        }
+    }
+
+    /// Parse a per-host probe list: `IP@probe,probe`
+    pub fn parse_host_spec(spec: &str) -> Result<(IpAddr, Vec<Self>)> {
+        let (ip, probes) = spec
+            .split_once('@')
+            .ok_or_else(|| anyhow!("Expected IP@probe[,probe...], got: {}", spec))?;
+        let ip: IpAddr = ip.parse().context(format!("Invalid IP address: {}", ip))?;
+
+        let probes = probes
+            .split(',')
+            .filter(|p| !p.is_empty())
+            .map(|p| Self::parse(p.trim()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((ip, probes))
+    }
+
+    /// Bind to a host, expanding `tcp` without a port to nmap's open TCP ports (or 80)
+    /// and resolving the scheme of `http` probes
+    pub fn expand(&self, scan: &ScannedHost, scheme: HttpScheme) -> Vec<Probe> {
+        let open_tcp_ports: Vec<u16> = scan
+            .open_ports()
+            .filter(|p| p.protocol == "tcp")
+            .map(|p| p.port)
+            .collect();
+
+        match self {
+            ProbeSpec::Icmp => vec![Probe::Icmp],
+            ProbeSpec::Http(port) => {
+                let tls = match scheme {
+                    HttpScheme::Http => false,
+                    HttpScheme::Https => true,
+                    HttpScheme::Auto => scan.is_tls_port(*port),
+                };
+                if tls {
+                    vec![Probe::Https(*port)]
+                } else {
+                    vec![Probe::Http(*port)]
+                }
+            }
+            ProbeSpec::Https(port) => vec![Probe::Https(*port)],
+            ProbeSpec::Tcp(Some(port)) => vec![Probe::Tcp(*port)],
+            ProbeSpec::Tcp(None) if open_tcp_ports.is_empty() => vec![Probe::Tcp(80)],
+            ProbeSpec::Tcp(None) => open_tcp_ports.iter().map(|p| Probe::Tcp(*p)).collect(),
+        }
+    }
+}
+
+/// Effective probe settings for one host
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProbeConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// ICMP payload size in bytes
+    pub payload_size: usize,
+    /// Bytes repeated to fill the ICMP payload
+    pub payload_pattern: Vec<u8>,
+    /// ICMP TTL / hop limit (`None` uses the OS default)
+    pub ttl: Option<u32>,
+    /// Hostname sent as TLS SNI and Host header instead of the bare IP
+    pub sni: Option<String>,
+    /// Accept self-signed, expired or mismatched certificates
+    pub accept_invalid_certs: bool,
+    pub method: HttpMethod,
+    /// Request path, including any query string
+    pub path: String,
+    /// Extra request headers, e.g. Host or Authorization
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    /// Status codes counted as successful requests (default 2xx/3xx)
+    pub success_status: Vec<RangeInclusive<u16>>,
+    /// Status codes counted as passing (`None` accepts any response)
+    pub expect_status: Option<Vec<RangeInclusive<u16>>>,
+    pub expect_body: Option<BodyMatch>,
+    /// Responses slower than this fail the check
+    pub max_response_time: Option<Duration>,
+    /// Download every body to measure its real size and detect content changes
+    pub read_body: bool,
+    /// Bytes of body read before the rest is skipped
+    pub max_body_size: usize,
+}
+
+impl ProbeConfig {
+    /// Built-in defaults for a mode (ICMP/TCP: 1s/2s, HTTP: 1s/5s)
+    pub fn defaults(mode: AppMode) -> Self {
+        let timeout = match mode {
+            AppMode::Icmp | AppMode::Tcp => Duration::from_secs(2),
+            AppMode::Http => Duration::from_secs(5),
+        };
+
+        Self {
+            interval: Duration::from_secs(1),
+            timeout,
+            payload_size: 56,
+            payload_pattern: vec![0],
+            ttl: None,
+            sni: None,
+            accept_invalid_certs: false,
+            method: HttpMethod::Get,
+            path: "/".to_string(),
+            headers: Vec::new(),
+            body: None,
+            success_status: vec![200..=399],
+            expect_status: None,
+            expect_body: None,
+            max_response_time: None,
+            read_body: false,
+            max_body_size: 1024 * 1024,
+        }
+    }
+
+    /// Apply any values set in `overrides` on top of this config
+    pub fn with(&self, overrides: &ProbeOverrides) -> Self {
+        Self {
+            interval: overrides.interval.unwrap_or(self.interval),
+            timeout: overrides.timeout.unwrap_or(self.timeout),
+            payload_size: overrides.payload_size.unwrap_or(self.payload_size),
+            payload_pattern: overrides
+                .payload_pattern
+                .clone()
+                .unwrap_or_else(|| self.payload_pattern.clone()),
+            ttl: overrides.ttl.or(self.ttl),
+            sni: overrides.sni.clone().or_else(|| self.sni.clone()),
+            accept_invalid_certs: overrides
+                .accept_invalid_certs
+                .unwrap_or(self.accept_invalid_certs),
+            method: overrides.method.unwrap_or(self.method),
+            path: overrides.path.clone().unwrap_or_else(|| self.path.clone()),
+            headers: merge_headers(&self.headers, &overrides.headers),
+            body: overrides.body.clone().or_else(|| self.body.clone()),
+            success_status: overrides
+                .success_status
+                .clone()
+                .unwrap_or_else(|| self.success_status.clone()),
+            expect_status: overrides
+                .expect_status
+                .clone()
+                .or_else(|| self.expect_status.clone()),
+            expect_body: overrides
+                .expect_body
+                .clone()
+                .or_else(|| self.expect_body.clone()),
+            max_response_time: overrides.max_response_time.or(self.max_response_time),
+            read_body: overrides.read_body.unwrap_or(self.read_body),
+            max_body_size: overrides.max_body_size.unwrap_or(self.max_body_size),
+        }
+    }
+
+    /// Whether a status code counts as a successful request
+    pub fn is_success(&self, code: u16) -> bool {
+        self.success_status.iter().any(|r| r.contains(&code))
+    }
+
+    /// Whether a status code passes the expected status set
+    pub fn status_expected(&self, code: u16) -> bool {
+        match &self.expect_status {
+            Some(ranges) => ranges.iter().any(|r| r.contains(&code)),
+            None => true,
+        }
+    }
+
+    /// Build the ICMP payload by repeating the pattern up to the payload size
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload_pattern
+            .iter()
+            .copied()
+            .cycle()
+            .take(self.payload_size)
+            .collect()
+    }
+}
+
+/// Partial probe settings, used for CLI flags and per-host overrides
+#[derive(Clone, Debug, Default)]
+pub struct ProbeOverrides {
+    pub interval: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub payload_size: Option<usize>,
+    pub payload_pattern: Option<Vec<u8>>,
+    pub ttl: Option<u32>,
+    pub sni: Option<String>,
+    pub accept_invalid_certs: Option<bool>,
+    pub method: Option<HttpMethod>,
+    pub path: Option<String>,
+    /// Headers added on top of (or replacing by name) the global ones
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub success_status: Option<Vec<RangeInclusive<u16>>>,
+    pub expect_status: Option<Vec<RangeInclusive<u16>>>,
+    pub expect_body: Option<BodyMatch>,
+    pub max_response_time: Option<Duration>,
+    pub read_body: Option<bool>,
+    pub max_body_size: Option<usize>,
+}
+
+impl ProbeOverrides {
+    /// Set one setting by name. Durations are in milliseconds, the pattern is hex.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "interval" => self.interval = Some(parse_millis(value)?),
+            "timeout" => self.timeout = Some(parse_millis(value)?),
+            "size" => {
+                self.payload_size = Some(value.parse().context(format!("Invalid size: {}", value))?)
+            }
+            "pattern" => self.payload_pattern = Some(parse_hex(value)?),
+            "ttl" => self.ttl = Some(value.parse().context(format!("Invalid TTL: {}", value))?),
+            "sni" => self.sni = Some(value.to_string()),
+            "insecure" => {
+                self.accept_invalid_certs =
+                    Some(value.parse().context(format!("Invalid boolean: {}", value))?)
+            }
+            "method" => self.method = Some(HttpMethod::parse(value)?),
+            "path" => {
+                if !value.starts_with('/') {
+                    return Err(anyhow!("Path must start with '/': {}", value));
+                }
+                self.path = Some(value.to_string())
+            }
+            "header" => {
+                let (name, header_value) = value
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Expected 'Name: value' header, got: {}", value))?;
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(anyhow!("Header name is empty: {}", value));
+                }
+                self.headers.push((name.to_string(), header_value.trim().to_string()));
+            }
+            "body" => self.body = Some(value.to_string()),
+            "success-status" => self.success_status = Some(parse_status_set(value)?),
+            "expect-status" => self.expect_status = Some(parse_status_set(value)?),
+            "expect-body" => self.expect_body = Some(BodyMatch::Contains(value.to_string())),
+            "expect-regex" => {
+                Regex::new(value).context(format!("Invalid regex: {}", value))?;
+                self.expect_body = Some(BodyMatch::Regex(value.to_string()))
+            }
+            "max-time" => self.max_response_time = Some(parse_millis(value)?),
+            "read-body" => {
+                self.read_body = Some(value.parse().context(format!("Invalid boolean: {}", value))?)
+            }
+            "max-body" => {
+                self.max_body_size =
+                    Some(value.parse().context(format!("Invalid size: {}", value))?)
+            }
+            _ => return Err(anyhow!("Unknown probe setting: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Parse a per-host override spec: `IP@key=value,key=value`.
+    /// A segment without `=` continues the previous value (e.g. `expect-status=200,204`).
+    pub fn parse_host_spec(spec: &str) -> Result<(IpAddr, Self)> {
+        let (ip, settings) = spec
+            .split_once('@')
+            .ok_or_else(|| anyhow!("Expected IP@key=value[,key=value...], got: {}", spec))?;
+        let ip: IpAddr = ip.parse().context(format!("Invalid IP address: {}", ip))?;
+
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        for segment in settings.split(',').filter(|p| !p.is_empty()) {
+            match (segment.split_once('='), pairs.last_mut()) {
+                (Some((key, value)), _) => pairs.push((key.trim().to_string(), value.to_string())),
+                (None, Some((_, value))) => {
+                    value.push(',');
+                    value.push_str(segment);
+                }
+                (None, None) => return Err(anyhow!("Expected key=value, got: {}", segment)),
+            }
+        }
+
+        let mut overrides = Self::default();
+        for (key, value) in pairs {
+            overrides.set(&key, value.trim())?;
+        }
+
+        Ok((ip, overrides))
+    }
+}
+
+fn parse_millis(value: &str) -> Result<Duration> {
+    let ms: u64 = value
+        .parse()
+        .context(format!("Invalid duration (milliseconds): {}", value))?;
+    if ms == 0 {
+        return Err(anyhow!("Duration must be greater than zero"));
+    }
+    Ok(Duration::from_millis(ms))
+}
+
+/// Parse a duration such as `90s`, `5m`, `1h` or `30d`; a bare number is seconds
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .context(format!("Invalid duration: {}", value))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(anyhow!("Invalid duration unit in {} (expected s, m, h or d)", value)),
+    };
+    let Some(secs) = number.checked_mul(unit_secs) else {
+        return Err(anyhow!("Duration too long: {}", value));
+    };
+    if secs == 0 {
+        return Err(anyhow!("Duration must be greater than zero"));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parse a status code set such as `200,204` or `200-299,301`
+fn parse_status_set(value: &str) -> Result<Vec<RangeInclusive<u16>>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (low, high) = part.split_once('-').unwrap_or((part, part));
+            let low: u16 = low.trim().parse().context(format!("Invalid status code: {}", part))?;
+            let high: u16 = high.trim().parse().context(format!("Invalid status code: {}", part))?;
+            if !(100..=599).contains(&low) || !(100..=599).contains(&high) || low > high {
+                return Err(anyhow!("Invalid status code range: {}", part));
+            }
+            Ok(low..=high)
+        })
+        .collect()
+}
+
+/// Overlay override headers on the base list, replacing headers with the same name
+fn merge_headers(
+    base: &[(String, String)],
+    overrides: &[(String, String)],
+) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = base
+        .iter()
+        .filter(|(name, _)| !overrides.iter().any(|(o, _)| o.eq_ignore_ascii_case(name)))
+        .cloned()
+        .collect();
+    headers.extend(overrides.iter().cloned());
+    headers
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>> {
+    let digits = value.trim_start_matches("0x");
+    if digits.is_empty()
+        || !digits.len().is_multiple_of(2)
+        || !digits.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err(anyhow!("Pattern must be an even number of hex digits: {}", value));
+    }
+
+    Ok((0..digits.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration(" 2h ").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("30d").unwrap(), Duration::from_secs(30 * 86400));
+        for bad in ["", "0", "0d", "5w", "m", "-1s", "1.5h"] {
+            assert!(parse_duration(bad).is_err(), "{:?}", bad);
+        }
+        // Too large for seconds in a u64 once scaled by the unit
+        assert!(parse_duration("999999999999999d").is_err());
+        assert!(parse_duration(&format!("{}s", u64::MAX)).is_ok());
+    }
+
+    #[test]
+    fn parses_hex_patterns() {
+        assert_eq!(parse_hex("0xdeadbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(parse_hex("00FF").unwrap(), vec![0x00, 0xff]);
+        for bad in ["", "0x", "abc", "zz", "0x1g"] {
+            assert!(parse_hex(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn parses_status_sets() {
+        assert_eq!(parse_status_set("200").unwrap(), vec![200..=200]);
+        assert_eq!(parse_status_set("200-299, 301").unwrap(), vec![200..=299, 301..=301]);
+        for bad in ["abc", "99", "600", "299-200", "200-", "-"] {
+            assert!(parse_status_set(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn override_values_continue_past_commas() {
+        let (ip, overrides) =
+            ProbeOverrides::parse_host_spec("10.0.0.1@expect-status=200,204-206,path=/health,pattern=0xab")
+                .unwrap();
+        assert_eq!(ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(overrides.expect_status, Some(vec![200..=200, 204..=206]));
+        assert_eq!(overrides.path.as_deref(), Some("/health"));
+        assert_eq!(overrides.payload_pattern, Some(vec![0xab]));
+
+        let (_, overrides) =
+            ProbeOverrides::parse_host_spec("::1@header=Accept: a/b,c/d,method=HEAD").unwrap();
+        assert_eq!(overrides.headers, vec![("Accept".to_string(), "a/b,c/d".to_string())]);
+        assert_eq!(overrides.method, Some(HttpMethod::Head));
+
+        for bad in [
+            "10.0.0.1",
+            "10.0.0.300@timeout=5",
+            "10.0.0.1@200,timeout=5",
+            "10.0.0.1@timeout=0",
+            "10.0.0.1@path=health",
+            "10.0.0.1@header=no colon",
+            "10.0.0.1@expect-regex=(",
+            "10.0.0.1@insecure=maybe",
+            "10.0.0.1@color=red",
+        ] {
+            assert!(ProbeOverrides::parse_host_spec(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn overrides_layer_on_the_defaults() {
+        let mut global = ProbeOverrides::default();
+        global.set("header", "Host: example.com").unwrap();
+        global.set("header", "X-Trace: 1").unwrap();
+        global.set("size", "5").unwrap();
+        let base = ProbeConfig::defaults(AppMode::Http).with(&global);
+
+        let (_, host) =
+            ProbeOverrides::parse_host_spec("10.0.0.1@header=host: other.example,pattern=0102").unwrap();
+        let config = base.with(&host);
+        assert_eq!(
+            config.headers,
+            vec![
+                ("X-Trace".to_string(), "1".to_string()),
+                ("host".to_string(), "other.example".to_string()),
+            ]
+        );
+        assert_eq!(config.timeout, Duration::from_secs(5));
+        assert_eq!(config.payload(), vec![1, 2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn parses_probe_specs() {
+        assert_eq!(ProbeSpec::parse("ICMP").unwrap(), ProbeSpec::Icmp);
+        assert_eq!(ProbeSpec::parse("http").unwrap(), ProbeSpec::Http(80));
+        assert_eq!(ProbeSpec::parse("https:8443").unwrap(), ProbeSpec::Https(8443));
+        assert_eq!(ProbeSpec::parse("tcp").unwrap(), ProbeSpec::Tcp(None));
+        for bad in ["icmp:1", "tcp:70000", "udp:53", ""] {
+            assert!(ProbeSpec::parse(bad).is_err(), "{:?}", bad);
+        }
+
+        let (ip, probes) = ProbeSpec::parse_host_spec("192.0.2.7@icmp, tcp:22").unwrap();
+        assert_eq!(ip, IpAddr::from([192, 0, 2, 7]));
+        assert_eq!(probes, vec![ProbeSpec::Icmp, ProbeSpec::Tcp(Some(22))]);
+        assert!(ProbeSpec::parse_host_spec("192.0.2.7").is_err());
+    }
+}
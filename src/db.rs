@@ -0,0 +1,429 @@
+use crate::history::{Bucket, HistoryCursor, Resolution, ResultDetail, StatusChange, Timeline, HISTORY_SPAN};
+use crate::messages::MessageSender;
+use crate::stats::{Probe, StatsMap};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeDelta, TimeZone};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Rows are written at least this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rows per transaction before flushing early
+const MAX_BATCH: usize = 1000;
+
+/// How often rows past the retention period are deleted
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    CREATE TABLE IF NOT EXISTS results (
+        ip TEXT NOT NULL,
+        probe TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        latency_us INTEGER,
+        status_code INTEGER,
+        success INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS results_at ON results (at_ms);
+    CREATE INDEX IF NOT EXISTS results_probe_at ON results (ip, probe, at_ms);
+    CREATE TABLE IF NOT EXISTS status_changes (
+        ip TEXT NOT NULL,
+        probe TEXT NOT NULL,
+        at_ms INTEGER NOT NULL,
+        from_status TEXT NOT NULL,
+        to_status TEXT NOT NULL,
+        duration_ms INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS status_changes_at ON status_changes (at_ms);
+";
+
+/// A row on its way to the writer thread
+enum Record {
+    Result {
+        ip: IpAddr,
+        probe: Probe,
+        at: DateTime<Local>,
+        latency: Option<Duration>,
+        status_code: Option<u16>,
+        success: bool,
+    },
+    Change {
+        ip: IpAddr,
+        probe: Probe,
+        change: StatusChange,
+    },
+}
+
+/// SQLite store of every probe result and status change.
+///
+/// New history is picked up from the stats map on each app tick and handed to
+/// a writer thread that commits it in batches, so neither the UI nor the probe
+/// tasks ever wait on disk.
+pub struct Database {
+    sender: Option<mpsc::Sender<Record>>,
+    writer: Option<thread::JoinHandle<()>>,
+    cursor: HistoryCursor,
+}
+
+impl Database {
+    /// Open (or create) the database, prune it, reload recent history into
+    /// `stats` and start the writer, which reports write failures to `messages`
+    pub fn open(
+        path: &Path,
+        retention: Duration,
+        stats: &StatsMap,
+        messages: MessageSender,
+    ) -> Result<Self> {
+        let connection = Connection::open(path)
+            .context(format!("Failed to open database: {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .context("Failed to create database tables")?;
+
+        let retention = TimeDelta::from_std(retention).unwrap_or(TimeDelta::MAX);
+        prune(&connection, retention).context("Failed to prune database")?;
+        restore(&connection, stats, retention.min(HISTORY_SPAN))
+            .context("Failed to load history from database")?;
+
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::spawn(move || write_batches(connection, receiver, retention, messages));
+
+        Ok(Self {
+            sender: Some(sender),
+            writer: Some(writer),
+            // Restored history is already on disk
+            cursor: HistoryCursor::new(stats),
+        })
+    }
+
+    /// Queue everything recorded since the last call
+    pub fn record(&mut self, stats: &StatsMap) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let stats_lock = stats.read();
+        for (&(ip, probe), stats) in stats_lock.iter() {
+            let key = (ip, probe);
+            for sample in self.cursor.new_results(key, stats.timeline()) {
+                let _ = sender.send(Record::Result {
+                    ip,
+                    probe,
+                    at: sample.at,
+                    latency: sample.latency,
+                    status_code: sample.detail.status_code,
+                    success: sample.detail.success,
+                });
+            }
+            for change in self.cursor.new_changes(key, stats.status_log()) {
+                let _ = sender.send(Record::Change { ip, probe, change });
+            }
+        }
+    }
+
+    /// Flush what's queued and wait for the writer to finish
+    pub async fn close(mut self) {
+        // Dropping the sender ends the writer loop after a final flush
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = tokio::task::spawn_blocking(move || writer.join()).await;
+        }
+    }
+}
+
+/// Reload history newer than `span` into the matching probes. Results in the
+/// raw window are replayed one by one; older ones only come back as rollups
+/// and counts, aggregated by SQLite. Rows for hosts or probes not in this
+/// session are skipped.
+fn restore(connection: &Connection, stats: &StatsMap, span: TimeDelta) -> Result<()> {
+    let now = Local::now();
+    let cutoff = (now - span).timestamp_millis();
+    let raw_start = Timeline::raw_window_start(now).timestamp_millis().max(cutoff);
+    let mut stats_lock = stats.write();
+    let keys: HashMap<(String, String), (IpAddr, Probe)> = stats_lock
+        .keys()
+        .map(|&(ip, probe)| ((ip.to_string(), probe.to_string()), (ip, probe)))
+        .collect();
+
+    let mut query = connection.prepare(
+        "SELECT ip, probe, status_code, COUNT(*), COUNT(latency_us), SUM(success)
+         FROM results WHERE at_ms >= ?1 AND at_ms < ?2
+         GROUP BY ip, probe, status_code",
+    )?;
+    let mut rows = query.query(params![cutoff, raw_start])?;
+    while let Some(row) = rows.next()? {
+        let Some(key) = keys.get(&(row.get(0)?, row.get(1)?)) else {
+            continue;
+        };
+        let (results, answered, successful): (i64, i64, i64) = (row.get(3)?, row.get(4)?, row.get(5)?);
+        if let Some(stats) = stats_lock.get_mut(key) {
+            stats.restore_counts(row.get(2)?, results as u64, answered as u64, successful as u64);
+        }
+    }
+
+    let minute_cutoff = (now - TimeDelta::days(1)).timestamp_millis().max(cutoff);
+    for (resolution, from, width_ms) in [
+        (Resolution::Minute, minute_cutoff, 60_000),
+        (Resolution::Hour, cutoff, 3_600_000),
+    ] {
+        let mut query = connection.prepare(
+            "SELECT ip, probe, at_ms / ?3 * ?3 AS start, COUNT(*), COUNT(*) - COUNT(latency_us),
+                    MIN(latency_us), MAX(latency_us), SUM(latency_us)
+             FROM results WHERE at_ms >= ?1 AND at_ms < ?2
+             GROUP BY ip, probe, start ORDER BY start",
+        )?;
+        let mut rows = query.query(params![from, raw_start, width_ms])?;
+        while let Some(row) = rows.next()? {
+            let Some(key) = keys.get(&(row.get(0)?, row.get(1)?)) else {
+                continue;
+            };
+            let Some(start) = from_millis(row.get(2)?) else {
+                continue;
+            };
+            let bucket = Bucket::with_totals(
+                start,
+                row.get(3)?,
+                row.get(4)?,
+                row.get::<_, Option<i64>>(5)?.map(micros),
+                row.get::<_, Option<i64>>(6)?.map(micros),
+                row.get::<_, Option<i64>>(7)?.map_or(Duration::ZERO, micros),
+            );
+            if let Some(stats) = stats_lock.get_mut(key) {
+                stats.restore_bucket(resolution, bucket);
+            }
+        }
+    }
+
+    let mut query = connection.prepare(
+        "SELECT ip, probe, at_ms, latency_us, status_code, success
+         FROM results WHERE at_ms >= ?1 ORDER BY at_ms",
+    )?;
+    let mut rows = query.query(params![raw_start])?;
+    while let Some(row) = rows.next()? {
+        let Some(key) = keys.get(&(row.get(0)?, row.get(1)?)) else {
+            continue;
+        };
+        let Some(at) = from_millis(row.get(2)?) else {
+            continue;
+        };
+        let latency: Option<i64> = row.get(3)?;
+        let detail = ResultDetail {
+            status_code: row.get(4)?,
+            success: row.get(5)?,
+            ..ResultDetail::default()
+        };
+        if let Some(stats) = stats_lock.get_mut(key) {
+            stats.restore(at, latency.map(micros), detail);
+        }
+    }
+
+    let mut query = connection.prepare(
+        "SELECT ip, probe, at_ms, from_status, to_status, duration_ms FROM status_changes
+         WHERE at_ms >= ?1 ORDER BY at_ms",
+    )?;
+    let mut rows = query.query(params![cutoff])?;
+    while let Some(row) = rows.next()? {
+        let Some(key) = keys.get(&(row.get(0)?, row.get(1)?)) else {
+            continue;
+        };
+        let Some(at) = from_millis(row.get(2)?) else {
+            continue;
+        };
+        let duration: Option<i64> = row.get(5)?;
+        if let Some(stats) = stats_lock.get_mut(key) {
+            stats.restore_change(StatusChange {
+                at,
+                from: row.get(3)?,
+                to: row.get(4)?,
+                duration: duration.map(|ms| Duration::from_millis(ms as u64)),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Writer thread: commit queued rows in batches and prune old ones now and then
+fn write_batches(
+    mut connection: Connection,
+    records: mpsc::Receiver<Record>,
+    retention: TimeDelta,
+    messages: MessageSender,
+) {
+    let mut batch = Vec::new();
+    let mut last_flush = Instant::now();
+    let mut last_prune = Instant::now();
+
+    loop {
+        let done = match records.recv_timeout(FLUSH_INTERVAL) {
+            Ok(record) => {
+                batch.push(record);
+                false
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => false,
+            Err(mpsc::RecvTimeoutError::Disconnected) => true,
+        };
+
+        if done || batch.len() >= MAX_BATCH || last_flush.elapsed() >= FLUSH_INTERVAL {
+            if !batch.is_empty() {
+                if let Err(e) = insert(&mut connection, &batch) {
+                    messages.error(format!("Failed to write history to database: {:#}", e));
+                }
+                batch.clear();
+            }
+            last_flush = Instant::now();
+        }
+        if last_prune.elapsed() >= PRUNE_INTERVAL {
+            if let Err(e) = prune(&connection, retention) {
+                messages.error(format!("Failed to prune database: {:#}", e));
+            }
+            last_prune = Instant::now();
+        }
+        if done {
+            break;
+        }
+    }
+}
+
+fn insert(connection: &mut Connection, batch: &[Record]) -> Result<()> {
+    let transaction = connection.transaction()?;
+    {
+        let mut insert_result = transaction.prepare_cached(
+            "INSERT INTO results (ip, probe, at_ms, latency_us, status_code, success)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut insert_change = transaction.prepare_cached(
+            "INSERT INTO status_changes (ip, probe, at_ms, from_status, to_status, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for record in batch {
+            match record {
+                Record::Result {
+                    ip,
+                    probe,
+                    at,
+                    latency,
+                    status_code,
+                    success,
+                } => {
+                    insert_result.execute(params![
+                        ip.to_string(),
+                        probe.to_string(),
+                        at.timestamp_millis(),
+                        latency.map(|d| d.as_micros() as i64),
+                        status_code,
+                        success,
+                    ])?;
+                }
+                Record::Change { ip, probe, change } => {
+                    insert_change.execute(params![
+                        ip.to_string(),
+                        probe.to_string(),
+                        change.at.timestamp_millis(),
+                        change.from,
+                        change.to,
+                        change.duration.map(|d| d.as_millis() as i64),
+                    ])?;
+                }
+            }
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Delete rows older than the retention period
+fn prune(connection: &Connection, retention: TimeDelta) -> Result<()> {
+    let cutoff = Local::now()
+        .checked_sub_signed(retention)
+        .map(|t| t.timestamp_millis())
+        .unwrap_or(i64::MIN);
+    connection.execute("DELETE FROM results WHERE at_ms < ?1", params![cutoff])?;
+    connection.execute("DELETE FROM status_changes WHERE at_ms < ?1", params![cutoff])?;
+    Ok(())
+}
+
+fn from_millis(ms: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_millis_opt(ms).single()
+}
+
+fn micros(us: i64) -> Duration {
+    Duration::from_micros(us as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+
+    fn result(at: DateTime<Local>, latency_ms: Option<u64>, status_code: Option<u16>, success: bool) -> Record {
+        Record::Result {
+            ip: "127.0.0.1".parse().unwrap(),
+            probe: Probe::Http(80),
+            at,
+            latency: latency_ms.map(Duration::from_millis),
+            status_code,
+            success,
+        }
+    }
+
+    fn http_stats() -> StatsMap {
+        let stats = StatsMap::default();
+        let probe = Probe::Http(80);
+        stats.write().insert(("127.0.0.1".parse().unwrap(), probe), probe.new_stats());
+        stats
+    }
+
+    #[test]
+    fn restores_rollups_counts_and_raw_results() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        let now = Local::now();
+        let old = now - TimeDelta::hours(2);
+        let recent = now - TimeDelta::minutes(1);
+        insert(
+            &mut connection,
+            &[
+                result(old, Some(10), Some(200), true),
+                // A 404 the probe expected
+                result(old, Some(20), Some(404), true),
+                result(old, None, None, false),
+                result(recent, Some(5), Some(404), false),
+                result(recent, None, None, false),
+                result(recent, None, None, false),
+                result(recent, None, None, false),
+            ],
+        )
+        .unwrap();
+
+        let stats = http_stats();
+        restore(&connection, &stats, HISTORY_SPAN).unwrap();
+        let stats_lock = stats.read();
+        let Some(Stats::Http(http)) = stats_lock.values().next() else {
+            panic!("missing HTTP stats");
+        };
+        assert_eq!(http.requests_sent, 7);
+        assert_eq!(http.requests_successful, 2);
+        assert_eq!(http.network_errors, 4);
+        assert_eq!(http.status_counts.get(&200), Some(&1));
+        assert_eq!(http.status_counts.get(&404), Some(&2));
+
+        let timeline = http.timeline();
+        let first = timeline.minutes().next().unwrap();
+        assert_eq!((first.samples, first.lost), (3, 1));
+        assert_eq!(first.avg(), Some(Duration::from_millis(15)));
+        assert_eq!(timeline.summarize(now - TimeDelta::hours(3)).samples, 7);
+
+        // Only the raw window comes back result by result, and none of it is live
+        assert_eq!(timeline.since(old, Resolution::Raw).len(), 4);
+        assert_eq!(timeline.trailing_lost(), 3);
+        assert_eq!(timeline.trailing_lost_live(), 0);
+        assert_eq!(timeline.live_since(), None);
+    }
+}
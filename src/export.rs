@@ -0,0 +1,1068 @@
+use crate::app::{App, Host};
+use crate::config::{ExportFormat, ExportSettings};
+use crate::history::{HistoryPoint, Resolution};
+use crate::http_stats::CertInfo;
+use crate::latency::LatencySpread;
+use crate::parser::ScannedHost;
+use crate::stats::{AppMode, Probe, Stats};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeDelta};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Newest events listed in a Markdown report
+const MARKDOWN_EVENTS: usize = 100;
+
+const CHART_WIDTH: f64 = 640.0;
+const CHART_HEIGHT: f64 = 140.0;
+/// Room for the y-axis labels on the left and the x-axis labels below
+const CHART_LEFT: f64 = 56.0;
+const CHART_BOTTOM: f64 = 20.0;
+
+/// Writes a report in one format, returning the files it created
+trait Exporter {
+    fn write(&self, report: &Report, base: &Path) -> Result<Vec<PathBuf>>;
+}
+
+struct CsvExporter;
+struct JsonExporter;
+struct MarkdownExporter;
+struct HtmlExporter;
+
+fn exporter(format: ExportFormat) -> Box<dyn Exporter> {
+    match format {
+        ExportFormat::Csv => Box::new(CsvExporter),
+        ExportFormat::Json => Box::new(JsonExporter),
+        ExportFormat::Markdown => Box::new(MarkdownExporter),
+        ExportFormat::Html => Box::new(HtmlExporter),
+    }
+}
+
+/// Snapshot the app and write it in each of `formats` under the configured
+/// directory and name, returning the paths written
+pub fn export(
+    app: &App,
+    settings: &ExportSettings,
+    formats: &[ExportFormat],
+) -> Result<Vec<PathBuf>> {
+    let report = Report::build(app);
+    let name = settings.name.replace(
+        "{ts}",
+        &report.generated.format("%Y%m%d_%H%M%S").to_string(),
+    );
+    fs::create_dir_all(&settings.dir).context(format!(
+        "Failed to create export directory: {}",
+        settings.dir.display()
+    ))?;
+    let base = settings.dir.join(name);
+
+    let mut files = Vec::new();
+    for format in formats {
+        files.extend(exporter(*format).write(&report, &base)?);
+    }
+    Ok(files)
+}
+
+/// Rows of text cells with a header, shared by every format
+struct Table {
+    title: &'static str,
+    /// File name suffix for the CSV export
+    name: &'static str,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Latency history of one probe for the report charts
+struct Chart {
+    title: String,
+    window: &'static str,
+    start: DateTime<Local>,
+    points: Vec<HistoryPoint>,
+}
+
+/// Everything an export contains, gathered once so every format agrees
+struct Report {
+    generated: DateTime<Local>,
+    summary: Vec<(&'static str, String)>,
+    /// One compact row per started probe
+    overview: Table,
+    /// Every column, one table per probe kind
+    details: Vec<Table>,
+    history: Table,
+    /// Status changes, oldest first
+    events: Table,
+    charts: Vec<Chart>,
+    json: Value,
+}
+
+impl Report {
+    fn build(app: &App) -> Self {
+        let generated = Local::now();
+        let details = app
+            .active_modes()
+            .into_iter()
+            .map(|mode| detail_table(app, mode))
+            .collect();
+        let events = app.events();
+
+        let mut overview = Table {
+            title: "Probes",
+            name: "overview",
+            headers: vec![
+                "IP",
+                "Hostname",
+                "Probe",
+                "Status",
+                "Last (ms)",
+                "Avg (ms)",
+                "Loss %",
+                "Sent",
+            ],
+            rows: Vec::new(),
+        };
+        let mut history = Table {
+            title: "History",
+            name: "history",
+            headers: vec![
+                "IP", "Hostname", "Probe", "Minute", "Samples", "Lost", "Loss %", "Min (ms)",
+                "Avg (ms)", "Max (ms)",
+            ],
+            rows: Vec::new(),
+        };
+        let mut charts = Vec::new();
+        let (mut started, mut up) = (0, 0);
+        let mut history_json = Vec::new();
+
+        {
+            let stats_lock = app.stats.read();
+            for host in &app.hosts {
+                let hostname = app.hostname(&host.ip);
+                for probe in &host.probes {
+                    let Some(stats) = stats_lock.get(&(host.ip, *probe)) else {
+                        continue;
+                    };
+
+                    for bucket in stats.timeline().minutes() {
+                        history.rows.push(vec![
+                            host.ip.to_string(),
+                            text_or_dash(hostname.as_deref()),
+                            probe.to_string(),
+                            bucket.start.format("%Y-%m-%d %H:%M").to_string(),
+                            bucket.samples.to_string(),
+                            bucket.lost.to_string(),
+                            format!("{:.2}", bucket.loss_percent()),
+                            ms(bucket.min),
+                            ms(bucket.avg()),
+                            ms(bucket.max),
+                        ]);
+                        history_json.push(json!({
+                            "ip": host.ip.to_string(),
+                            "probe": probe.to_string(),
+                            "minute": bucket.start.to_rfc3339(),
+                            "samples": bucket.samples,
+                            "lost": bucket.lost,
+                            "min_ms": bucket.min.map(millis),
+                            "avg_ms": bucket.avg().map(millis),
+                            "max_ms": bucket.max.map(millis),
+                        }));
+                    }
+
+                    let (status, last, avg, loss, sent) = overview_fields(stats);
+                    if sent == 0 {
+                        continue;
+                    }
+                    started += 1;
+                    if stats.timeline().trailing_lost() == 0 {
+                        up += 1;
+                    }
+                    overview.rows.push(vec![
+                        host.ip.to_string(),
+                        text_or_dash(hostname.as_deref()),
+                        probe.to_string(),
+                        status,
+                        ms(last),
+                        ms(avg),
+                        format!("{:.1}", loss),
+                        sent.to_string(),
+                    ]);
+
+                    if let Some(chart) = chart(
+                        host.ip.to_string(),
+                        hostname.as_deref(),
+                        *probe,
+                        stats,
+                        generated,
+                    ) {
+                        charts.push(chart);
+                    }
+                }
+            }
+        }
+
+        let events_table = Table {
+            title: "Events",
+            name: "events",
+            headers: vec![
+                "Time",
+                "IP",
+                "Hostname",
+                "Probe",
+                "From",
+                "To",
+                "Previous Duration (s)",
+            ],
+            rows: events
+                .iter()
+                .rev()
+                .map(|event| {
+                    vec![
+                        event.change.at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        event.ip.to_string(),
+                        text_or_dash(app.hostname(&event.ip).as_deref()),
+                        event.probe.to_string(),
+                        event.change.from.clone(),
+                        event.change.to.clone(),
+                        event
+                            .change
+                            .duration
+                            .map(|d| format!("{:.1}", d.as_secs_f64()))
+                            .unwrap_or_else(|| "-".to_string()),
+                    ]
+                })
+                .collect(),
+        };
+
+        let summary = vec![
+            ("Hosts", app.hosts.len().to_string()),
+            (
+                "Monitored",
+                app.hosts.iter().filter(|h| h.selected).count().to_string(),
+            ),
+            ("Probes answering", format!("{} of {}", up, started)),
+            ("Status changes", events.len().to_string()),
+            ("Alerts firing", app.alerts.active().count().to_string()),
+            ("Paused", if app.paused { "yes" } else { "no" }.to_string()),
+        ];
+
+        let hosts: Vec<Value> = app
+            .hosts
+            .iter()
+            .map(|host| {
+                let mut value = host_json(app, host);
+                value["scan"] = scan_json(&host.scan);
+                value
+            })
+            .collect();
+        let json = json!({
+            "generated": generated.to_rfc3339(),
+            "summary": {
+                "hosts": app.hosts.len(),
+                "monitored": app.hosts.iter().filter(|h| h.selected).count(),
+                "probes_started": started,
+                "probes_answering": up,
+                "alerts_firing": app.alerts.active().count(),
+                "paused": app.paused,
+            },
+            "hosts": hosts,
+            "events": events
+                .iter()
+                .rev()
+                .map(|event| json!({
+                    "time": event.change.at.to_rfc3339(),
+                    "ip": event.ip.to_string(),
+                    "probe": event.probe.to_string(),
+                    "from": event.change.from,
+                    "to": event.change.to,
+                    "previous_duration_secs": event.change.duration.map(|d| d.as_secs_f64()),
+                }))
+                .collect::<Vec<_>>(),
+            "history": history_json,
+        });
+
+        Self {
+            generated,
+            summary,
+            overview,
+            details,
+            history,
+            events: events_table,
+            charts,
+            json,
+        }
+    }
+}
+
+/// Status, last and average latency, failure percentage and probes sent
+fn overview_fields(stats: &Stats) -> (String, Option<Duration>, Option<Duration>, f64, u64) {
+    match stats {
+        Stats::Ping(s) => (
+            format!("{:?}", s.status),
+            s.last_latency,
+            s.avg_latency,
+            s.packet_loss_percent,
+            s.packets_sent,
+        ),
+        Stats::Http(s) => (
+            format!("{:?}", s.status),
+            s.last_response_time,
+            s.avg_response_time,
+            100.0 - s.success_rate_percent,
+            s.requests_sent,
+        ),
+        Stats::Tcp(s) => (
+            format!("{:?}", s.status),
+            s.last_connect_time,
+            s.avg_connect_time,
+            100.0 - s.success_rate_percent,
+            s.attempts,
+        ),
+    }
+}
+
+/// Last hour in 1-minute averages, or up to 10 minutes of raw samples in a younger session
+fn chart(
+    ip: String,
+    hostname: Option<&str>,
+    probe: Probe,
+    stats: &Stats,
+    now: DateTime<Local>,
+) -> Option<Chart> {
+    let timeline = stats.timeline();
+    let hour_ago = now - TimeDelta::hours(1);
+    let minutes = timeline.since(hour_ago, Resolution::Minute);
+    let (window, start, points) = if minutes.len() >= 3 {
+        ("last hour, 1-min averages", hour_ago, minutes)
+    } else {
+        let points = timeline.since(now - TimeDelta::minutes(10), Resolution::Raw);
+        let start = points.first().map_or(now, |p| p.at);
+        ("last 10 minutes", start, points)
+    };
+    if points.is_empty() {
+        return None;
+    }
+    let title = match hostname {
+        Some(name) => format!("{} ({}) {}", ip, name, probe),
+        None => format!("{} {}", ip, probe),
+    };
+    Some(Chart {
+        title,
+        window,
+        start,
+        points,
+    })
+}
+
+/// Every statistic of one probe kind, as the CSV export has always had it
+fn detail_table(app: &App, mode: AppMode) -> Table {
+    let stats_lock = app.stats.read();
+    let mut rows = Vec::new();
+
+    for (host, probe) in app.host_probes(mode) {
+        let Some(stats) = stats_lock.get(&(host.ip, probe)) else {
+            continue;
+        };
+        let hostname = text_or_dash(app.hostname(&host.ip).as_deref());
+        let mut row = match (stats, probe) {
+            (Stats::Ping(s), _) => {
+                let mut row = vec![
+                    host.ip.to_string(),
+                    hostname,
+                    format!("{:?}", s.status),
+                    ms(s.last_latency),
+                    ms(s.avg_latency),
+                    ms(s.min_latency),
+                    ms(s.max_latency),
+                ];
+                row.extend(spread_cells(s.spread.as_ref()));
+                row.extend([
+                    format!("{:.2}", s.packet_loss_percent),
+                    s.packets_sent.to_string(),
+                    s.packets_received.to_string(),
+                ]);
+                row
+            }
+            (Stats::Http(s), _) => {
+                let mut row = vec![
+                    host.ip.to_string(),
+                    hostname,
+                    probe.to_string(),
+                    format!("{:?}", s.status),
+                    s.last_status_code
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    ms(s.last_response_time),
+                    ms(s.avg_response_time),
+                    ms(s.min_response_time),
+                    ms(s.max_response_time),
+                ];
+                row.extend(spread_cells(s.spread.as_ref()));
+                row.extend([
+                    s.last_content_size
+                        .map(|size| size.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    s.content_truncated.to_string(),
+                    s.content_hash
+                        .map(|h| format!("{:016x}", h))
+                        .unwrap_or_else(|| "-".to_string()),
+                    s.content_changes.to_string(),
+                    s.last_content_change
+                        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    format!("{:.2}", s.success_rate_percent),
+                    s.requests_sent.to_string(),
+                    s.requests_successful.to_string(),
+                ]);
+                row.extend((1..=5).map(|class| s.class_count(class).to_string()));
+                row.extend([
+                    s.network_errors.to_string(),
+                    s.status_counts
+                        .iter()
+                        .map(|(code, count)| format!("{}:{}", code, count))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                    text_or_dash(s.last_error.as_deref()),
+                ]);
+                row.extend(cert_cells(s.cert.as_ref()));
+                row
+            }
+            (Stats::Tcp(s), Probe::Tcp(port)) => vec![
+                host.ip.to_string(),
+                hostname,
+                port.to_string(),
+                format!("{:?}", s.status),
+                ms(s.last_connect_time),
+                ms(s.avg_connect_time),
+                ms(s.min_connect_time),
+                ms(s.max_connect_time),
+                format!("{:.2}", s.success_rate_percent),
+                s.attempts.to_string(),
+                s.successful.to_string(),
+                s.refused.to_string(),
+                s.timeouts.to_string(),
+                text_or_dash(s.last_error.as_deref()),
+            ],
+            _ => continue,
+        };
+        row.extend(scan_cells(&host.scan));
+        rows.push(row);
+    }
+
+    let spread = [
+        "P50 (ms)",
+        "P90 (ms)",
+        "P95 (ms)",
+        "P99 (ms)",
+        "Std Dev (ms)",
+        "Jitter (ms)",
+    ];
+    let scan = ["Host State", "MAC", "Vendor", "Open Ports"];
+    let (title, name, headers): (_, _, Vec<&'static str>) = match mode {
+        AppMode::Icmp => (
+            "ICMP",
+            "icmp",
+            [
+                "IP",
+                "Hostname",
+                "Status",
+                "Last Latency (ms)",
+                "Avg Latency (ms)",
+                "Min Latency (ms)",
+                "Max Latency (ms)",
+            ]
+            .into_iter()
+            .chain(spread)
+            .chain(["Packet Loss %", "Packets Sent", "Packets Received"])
+            .chain(scan)
+            .collect(),
+        ),
+        AppMode::Http => (
+            "HTTP",
+            "http",
+            [
+                "IP",
+                "Hostname",
+                "Probe",
+                "Status",
+                "Status Code",
+                "Last Response Time (ms)",
+                "Avg Response Time (ms)",
+                "Min Response Time (ms)",
+                "Max Response Time (ms)",
+            ]
+            .into_iter()
+            .chain(spread)
+            .chain([
+                "Content Size",
+                "Content Truncated",
+                "Content Hash",
+                "Content Changes",
+                "Last Content Change",
+                "Success Rate %",
+                "Requests Sent",
+                "Requests Successful",
+                "1xx",
+                "2xx",
+                "3xx",
+                "4xx",
+                "5xx",
+                "Network Errors",
+                "Status Codes",
+                "Last Error",
+                "Cert Subject",
+                "Cert Issuer",
+                "Cert Days Left",
+            ])
+            .chain(scan)
+            .collect(),
+        ),
+        AppMode::Tcp => (
+            "TCP",
+            "tcp",
+            [
+                "IP",
+                "Hostname",
+                "Port",
+                "Status",
+                "Last Connect (ms)",
+                "Avg Connect (ms)",
+                "Min Connect (ms)",
+                "Max Connect (ms)",
+                "Success Rate %",
+                "Attempts",
+                "Successful",
+                "Refused",
+                "Timeouts",
+                "Last Error",
+            ]
+            .into_iter()
+            .chain(scan)
+            .collect(),
+        ),
+    };
+
+    Table {
+        title,
+        name,
+        headers,
+        rows,
+    }
+}
+
+fn spread_cells(spread: Option<&LatencySpread>) -> Vec<String> {
+    match spread {
+        Some(s) => [s.p50, s.p90, s.p95, s.p99, s.std_dev, s.jitter]
+            .into_iter()
+            .map(|d| ms(Some(d)))
+            .collect(),
+        None => vec!["-".to_string(); 6],
+    }
+}
+
+/// Subject, issuer and days left
+fn cert_cells(cert: Option<&CertInfo>) -> Vec<String> {
+    match cert {
+        Some(cert) => vec![
+            cert.subject.clone(),
+            cert.issuer.clone(),
+            cert.days_until_expiry().to_string(),
+        ],
+        None => vec!["-".to_string(); 3],
+    }
+}
+
+/// nmap state, MAC, vendor and open ports
+fn scan_cells(scan: &ScannedHost) -> Vec<String> {
+    vec![
+        text_or_dash(scan.state.as_deref()),
+        text_or_dash(scan.mac.as_deref()),
+        text_or_dash(scan.mac_vendor.as_deref()),
+        open_ports(scan).join("; "),
+    ]
+}
+
+/// A host and the current stats of each of its probes, as served by the API and written to JSON exports
+pub fn host_json(app: &App, host: &Host) -> Value {
+    let stats_lock = app.stats.read();
+    let probes: Vec<Value> = host
+        .probes
+        .iter()
+        .filter_map(|probe| stats_lock.get(&(host.ip, *probe)).map(|s| probe_json(*probe, s)))
+        .collect();
+    json!({
+        "ip": host.ip.to_string(),
+        "hostname": app.hostname(&host.ip),
+        "selected": host.selected,
+        "probes": probes,
+    })
+}
+
+fn probe_json(probe: Probe, stats: &Stats) -> Value {
+    let ms = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+    match stats {
+        Stats::Ping(s) => json!({
+            "probe": probe.to_string(),
+            "status": format!("{:?}", s.status),
+            "last_ms": ms(s.last_latency),
+            "avg_ms": ms(s.avg_latency),
+            "min_ms": ms(s.min_latency),
+            "max_ms": ms(s.max_latency),
+            "loss_percent": s.packet_loss_percent,
+            "sent": s.packets_sent,
+            "received": s.packets_received,
+        }),
+        Stats::Http(s) => json!({
+            "probe": probe.to_string(),
+            "status": format!("{:?}", s.status),
+            "status_code": s.last_status_code,
+            "last_ms": ms(s.last_response_time),
+            "avg_ms": ms(s.avg_response_time),
+            "min_ms": ms(s.min_response_time),
+            "max_ms": ms(s.max_response_time),
+            "success_percent": s.success_rate_percent,
+            "sent": s.requests_sent,
+            "successful": s.requests_successful,
+            "error": s.last_error,
+        }),
+        Stats::Tcp(s) => json!({
+            "probe": probe.to_string(),
+            "status": format!("{:?}", s.status),
+            "last_ms": ms(s.last_connect_time),
+            "avg_ms": ms(s.avg_connect_time),
+            "min_ms": ms(s.min_connect_time),
+            "max_ms": ms(s.max_connect_time),
+            "success_percent": s.success_rate_percent,
+            "sent": s.attempts,
+            "successful": s.successful,
+            "error": s.last_error,
+        }),
+    }
+}
+
+fn scan_json(scan: &ScannedHost) -> Value {
+    json!({
+        "state": scan.state,
+        "mac": scan.mac,
+        "vendor": scan.mac_vendor,
+        "open_ports": open_ports(scan),
+    })
+}
+
+fn open_ports(scan: &ScannedHost) -> Vec<String> {
+    scan.open_ports()
+        .map(|p| match &p.service {
+            Some(service) => format!("{}/{} {}", p.port, p.protocol, service),
+            None => format!("{}/{}", p.port, p.protocol),
+        })
+        .collect()
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn ms(d: Option<Duration>) -> String {
+    d.map(|d| format!("{:.2}", millis(d)))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn text_or_dash(text: Option<&str>) -> String {
+    text.unwrap_or("-").to_string()
+}
+
+impl Exporter for CsvExporter {
+    /// One file per table: `<base>.csv` for the first probe kind, `<base>_<table>.csv` for the rest
+    fn write(&self, report: &Report, base: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let tables = report.details.iter().chain([&report.history, &report.events]);
+        for (i, table) in tables.enumerate() {
+            // The first probe kind keeps the plain `<base>.csv` name single-mode exports always had
+            let path = if i == 0 && !report.details.is_empty() {
+                with_suffix(base, ".csv")
+            } else {
+                with_suffix(base, &format!("_{}.csv", table.name))
+            };
+            let mut out = String::new();
+            write_csv_row(&mut out, table.headers.iter().copied());
+            for row in &table.rows {
+                write_csv_row(&mut out, row.iter().map(String::as_str));
+            }
+            write_file(&path, &out)?;
+            files.push(path);
+        }
+        Ok(files)
+    }
+}
+
+fn write_csv_row<'a>(out: &mut String, cells: impl Iterator<Item = &'a str>) {
+    let line: Vec<Cow<str>> = cells.map(csv_field).collect();
+    out.push_str(&line.join(","));
+    out.push_str("\r\n");
+}
+
+/// Quote a field if it holds a comma, quote, line break or edge whitespace (RFC 4180)
+fn csv_field(field: &str) -> Cow<'_, str> {
+    let needs_quotes =
+        field.contains([',', '"', '\n', '\r']) || field.starts_with(' ') || field.ends_with(' ');
+    if needs_quotes {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+impl Exporter for JsonExporter {
+    fn write(&self, report: &Report, base: &Path) -> Result<Vec<PathBuf>> {
+        let path = with_suffix(base, ".json");
+        write_file(&path, &format!("{:#}\n", report.json))?;
+        Ok(vec![path])
+    }
+}
+
+impl Exporter for MarkdownExporter {
+    fn write(&self, report: &Report, base: &Path) -> Result<Vec<PathBuf>> {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Network report");
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "Generated {}",
+            report.generated.format("%Y-%m-%d %H:%M:%S")
+        );
+        let _ = writeln!(out);
+        for (label, value) in &report.summary {
+            let _ = writeln!(out, "- **{}:** {}", label, value);
+        }
+
+        let _ = writeln!(out, "\n## {}\n", report.overview.title);
+        if report.overview.rows.is_empty() {
+            let _ = writeln!(out, "No probes have run yet.");
+        } else {
+            write_markdown_table(
+                &mut out,
+                &report.overview.headers,
+                report.overview.rows.iter(),
+            );
+        }
+
+        let _ = writeln!(out, "\n## {}\n", report.events.title);
+        if report.events.rows.is_empty() {
+            let _ = writeln!(out, "No status changes.");
+        } else {
+            if report.events.rows.len() > MARKDOWN_EVENTS {
+                let _ = writeln!(
+                    out,
+                    "Newest {} of {}, newest first.\n",
+                    MARKDOWN_EVENTS,
+                    report.events.rows.len()
+                );
+            }
+            write_markdown_table(
+                &mut out,
+                &report.events.headers,
+                report.events.rows.iter().rev().take(MARKDOWN_EVENTS),
+            );
+        }
+
+        for table in &report.details {
+            let _ = writeln!(out, "\n## {} details\n", table.title);
+            write_markdown_table(&mut out, &table.headers, table.rows.iter());
+        }
+
+        let path = with_suffix(base, ".md");
+        write_file(&path, &out)?;
+        Ok(vec![path])
+    }
+}
+
+fn write_markdown_table<'a>(
+    out: &mut String,
+    headers: &[&str],
+    rows: impl Iterator<Item = &'a Vec<String>>,
+) {
+    let _ = writeln!(out, "| {} |", headers.join(" | "));
+    let _ = writeln!(out, "|{}", "---|".repeat(headers.len()));
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|cell| cell.replace('|', "\\|").replace(['\r', '\n'], " "))
+            .collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+}
+
+impl Exporter for HtmlExporter {
+    /// A single file with styles and SVG charts inline, so it can be mailed or archived as is
+    fn write(&self, report: &Report, base: &Path) -> Result<Vec<PathBuf>> {
+        let generated = report.generated.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Network report {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            generated, HTML_STYLE
+        );
+        let _ = writeln!(
+            out,
+            "<h1>Network report</h1>\n<p class=\"muted\">Generated {}</p>",
+            generated
+        );
+
+        let _ = writeln!(out, "<dl class=\"summary\">");
+        for (label, value) in &report.summary {
+            let _ = writeln!(
+                out,
+                "<div><dt>{}</dt><dd>{}</dd></div>",
+                html(label),
+                html(value)
+            );
+        }
+        let _ = writeln!(out, "</dl>");
+
+        let _ = writeln!(out, "<h2>{}</h2>", report.overview.title);
+        write_html_table(
+            &mut out,
+            &report.overview,
+            report.overview.rows.iter(),
+            Some(3),
+        );
+
+        if !report.charts.is_empty() {
+            let _ = writeln!(out, "<h2>Latency</h2>\n<div class=\"charts\">");
+            for chart in &report.charts {
+                let _ = writeln!(
+                    out,
+                    "<figure>{}<figcaption>{} <span class=\"muted\">({})</span></figcaption></figure>",
+                    svg_chart(chart, report.generated),
+                    html(&chart.title),
+                    chart.window
+                );
+            }
+            let _ = writeln!(out, "</div>");
+        }
+
+        let _ = writeln!(out, "<h2>{}</h2>", report.events.title);
+        write_html_table(
+            &mut out,
+            &report.events,
+            report.events.rows.iter().rev(),
+            Some(5),
+        );
+
+        for table in &report.details {
+            let _ = writeln!(out, "<details>\n<summary>{} details</summary>", table.title);
+            write_html_table(&mut out, table, table.rows.iter(), None);
+            let _ = writeln!(out, "</details>");
+        }
+        let _ = writeln!(out, "</body>\n</html>");
+
+        let path = with_suffix(base, ".html");
+        write_file(&path, &out)?;
+        Ok(vec![path])
+    }
+}
+
+const HTML_STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h2 { margin-top: 1.6em; border-bottom: 1px solid #ddd; }
+.muted { color: #777; }
+.summary { display: flex; flex-wrap: wrap; gap: 1em; }
+.summary div { border: 1px solid #ddd; border-radius: 6px; padding: 0.5em 1em; }
+.summary dt { font-size: 0.8em; color: #777; }
+.summary dd { margin: 0; font-size: 1.4em; }
+.scroll { overflow-x: auto; }
+table { border-collapse: collapse; font-size: 0.9em; }
+th, td { border: 1px solid #ddd; padding: 0.25em 0.6em; text-align: left; white-space: nowrap; }
+th { background: #f4f4f4; }
+.ok { color: #1a7f37; }
+.bad { color: #cf222e; font-weight: bold; }
+.charts { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; }
+figcaption { font-size: 0.9em; }
+details { margin-top: 1em; }
+summary { cursor: pointer; font-weight: bold; }
+";
+
+/// Write a table, colouring the status cells in column `status_column`
+fn write_html_table<'a>(
+    out: &mut String,
+    table: &Table,
+    rows: impl Iterator<Item = &'a Vec<String>>,
+    status_column: Option<usize>,
+) {
+    let _ = write!(out, "<div class=\"scroll\"><table>\n<tr>");
+    for header in &table.headers {
+        let _ = write!(out, "<th>{}</th>", html(header));
+    }
+    let _ = writeln!(out, "</tr>");
+    for row in rows {
+        let _ = write!(out, "<tr>");
+        for (i, cell) in row.iter().enumerate() {
+            match status_column
+                .filter(|c| *c == i)
+                .and_then(|_| status_class(cell))
+            {
+                Some(class) => {
+                    let _ = write!(out, "<td class=\"{}\">{}</td>", class, html(cell));
+                }
+                None => {
+                    let _ = write!(out, "<td>{}</td>", html(cell));
+                }
+            }
+        }
+        let _ = writeln!(out, "</tr>");
+    }
+    let _ = writeln!(out, "</table></div>");
+}
+
+fn status_class(status: &str) -> Option<&'static str> {
+    match status {
+        "Active" | "Success" | "Open" => Some("ok"),
+        "Timeout" | "Unreachable" | "ClientError" | "ServerError" | "NetworkError"
+        | "AssertionFailed" | "Refused" | "Filtered" | "Error" => Some("bad"),
+        _ => None,
+    }
+}
+
+/// Line chart of latency over the chart window; lost samples are red ticks along the top
+fn svg_chart(chart: &Chart, end: DateTime<Local>) -> String {
+    let plot_width = CHART_WIDTH - CHART_LEFT - 8.0;
+    let plot_height = CHART_HEIGHT - CHART_BOTTOM - 8.0;
+    let span = (end - chart.start).num_milliseconds().max(1) as f64;
+    let y_max = chart
+        .points
+        .iter()
+        .filter_map(|p| p.latency)
+        .map(millis)
+        .fold(0.0, f64::max)
+        .max(1.0)
+        * 1.1;
+
+    let x = |at: DateTime<Local>| {
+        CHART_LEFT + (at - chart.start).num_milliseconds() as f64 / span * plot_width
+    };
+    let y = |latency: f64| 8.0 + plot_height - latency / y_max * plot_height;
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+         font-family=\"sans-serif\" font-size=\"10\">",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT
+    );
+    let _ = write!(
+        out,
+        "<rect x=\"{}\" y=\"8\" width=\"{}\" height=\"{}\" fill=\"#fafafa\" stroke=\"#ddd\"/>",
+        CHART_LEFT, plot_width, plot_height
+    );
+
+    // Break the line wherever a sample was lost
+    let mut segment: Vec<String> = Vec::new();
+    let mut segments = Vec::new();
+    for point in &chart.points {
+        match point.latency {
+            Some(latency) => segment.push(format!("{:.1},{:.1}", x(point.at), y(millis(latency)))),
+            None => segments.push(std::mem::take(&mut segment)),
+        }
+    }
+    segments.push(segment);
+    for segment in segments.iter().filter(|s| !s.is_empty()) {
+        let _ = write!(
+            out,
+            "<polyline fill=\"none\" stroke=\"#0969da\" stroke-width=\"1.5\" points=\"{}\"/>",
+            segment.join(" ")
+        );
+    }
+    for point in chart.points.iter().filter(|p| p.lost) {
+        let _ = write!(
+            out,
+            "<rect x=\"{:.1}\" y=\"8\" width=\"2\" height=\"6\" fill=\"#cf222e\"/>",
+            x(point.at) - 1.0
+        );
+    }
+
+    let _ = write!(
+        out,
+        "<text x=\"{l}\" y=\"14\" text-anchor=\"end\">{:.1} ms</text>\
+         <text x=\"{l}\" y=\"{}\" text-anchor=\"end\">0 ms</text>\
+         <text x=\"{}\" y=\"{}\">{}</text>\
+         <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text></svg>",
+        y_max,
+        8.0 + plot_height,
+        CHART_LEFT,
+        CHART_HEIGHT - 6.0,
+        chart.start.format("%H:%M"),
+        CHART_LEFT + plot_width,
+        CHART_HEIGHT - 6.0,
+        end.format("%H:%M"),
+        l = CHART_LEFT - 4.0,
+    );
+    out
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `base` with `suffix` appended to its file name
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+    let mut name = base.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    base.with_file_name(name)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).context(format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table {
+            title: "TCP",
+            name: "tcp",
+            headers: vec!["IP", "Status", "Last Error"],
+            rows: rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect(),
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_per_rfc_4180() {
+        let mut out = String::new();
+        write_csv_row(
+            &mut out,
+            ["plain", "a,b", "say \"hi\"", "two\nlines", "cr\rlf", " padded", ""].into_iter(),
+        );
+        assert_eq!(out, "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\rlf\",\" padded\",\r\n");
+        assert!(matches!(csv_field("10.0.0.1"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn html_cells_are_escaped() {
+        assert_eq!(html(r#"<b>"R&D"</b>"#), "&lt;b&gt;&quot;R&amp;D&quot;&lt;/b&gt;");
+
+        let mut out = String::new();
+        let table = table(&[&["10.0.0.1", "Refused", "<script>alert('x')</script> & more"]]);
+        write_html_table(&mut out, &table, table.rows.iter(), Some(1));
+        assert!(out.contains("<td class=\"bad\">Refused</td>"), "{}", out);
+        assert!(out.contains("<td>&lt;script&gt;alert('x')&lt;/script&gt; &amp; more</td>"), "{}", out);
+        assert!(!out.contains("<script>"));
+    }
+
+    #[test]
+    fn markdown_cells_stay_in_their_column() {
+        let mut out = String::new();
+        let table = table(&[&["10.0.0.1", "Error", "a | b\nc"]]);
+        write_markdown_table(&mut out, &table.headers, table.rows.iter());
+        assert_eq!(out, "| IP | Status | Last Error |\n|---|---|---|\n| 10.0.0.1 | Error | a \\| b c |\n");
+    }
+}
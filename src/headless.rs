@@ -0,0 +1,143 @@
+use crate::app::App;
+use crate::history::{HistoryCursor, ResultDetail};
+use crate::stats::Stats;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// How often new results are collected from the probe tasks
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Monitor the selected hosts without a terminal UI, writing one JSON object
+/// per line for every probe result, status change, alert and task message.
+///
+/// Runs until SIGINT or SIGTERM, then stops the probes through `App::shutdown`.
+pub async fn run(app: &mut App, output: Option<&Path>) -> Result<()> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .context(format!("Failed to open output file: {}", path.display()))?,
+        )),
+        // Locked per write, so other output such as the alert bell isn't held up for the whole run
+        None => Box::new(io::stdout()),
+    };
+
+    let mut sigterm = signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    let mut cursor = HistoryCursor::new(&app.stats);
+    let mut messages_seen = 0;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => break,
+            _ = sigterm.recv() => break,
+        }
+        write_records(app, &mut cursor, &mut messages_seen, &mut out)?;
+    }
+
+    app.shutdown().await;
+    // Results that landed while the probes were stopping
+    write_records(app, &mut cursor, &mut messages_seen, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Write everything new since the last call and flush it
+fn write_records(
+    app: &mut App,
+    cursor: &mut HistoryCursor,
+    messages_seen: &mut usize,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut records = Vec::new();
+    for event in app.tick() {
+        let mut record = event.to_json();
+        record["type"] = json!("alert");
+        records.push(record);
+    }
+
+    let (messages, seen) = app.messages.since(*messages_seen);
+    for message in messages {
+        records.push(json!({
+            "type": "message",
+            "time": message.at.to_rfc3339(),
+            "level": message.level.as_str(),
+            "text": message.text,
+        }));
+    }
+    *messages_seen = seen;
+
+    {
+        let stats_lock = app.stats.read();
+        for (&(ip, probe), stats) in stats_lock.iter() {
+            let key = (ip, probe);
+            let host = ip.to_string();
+            let hostname = app.hostname(&ip);
+
+            for sample in cursor.new_results(key, stats.timeline()) {
+                let mut record = json!({
+                    "type": "result",
+                    "time": sample.at.to_rfc3339(),
+                    "host": host,
+                    "hostname": hostname,
+                    "probe": probe.to_string(),
+                    "ok": sample.detail.success,
+                    "latency_ms": sample.latency.map(|d| d.as_secs_f64() * 1000.0),
+                });
+                let error = stats.timeline().error_at(sample.seq);
+                add_probe_details(&mut record, stats, &sample.detail, error);
+                records.push(record);
+            }
+
+            for change in cursor.new_changes(key, stats.status_log()) {
+                records.push(json!({
+                    "type": "transition",
+                    "time": change.at.to_rfc3339(),
+                    "host": host,
+                    "hostname": hostname,
+                    "probe": probe.to_string(),
+                    "from": change.from,
+                    "to": change.to,
+                    "previous_duration_secs": change.duration.map(|d| d.as_secs_f64()),
+                }));
+            }
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(());
+    }
+    records.sort_by(|a, b| a["time"].as_str().cmp(&b["time"].as_str()));
+    for record in records {
+        // One write per record keeps each line whole
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        out.write_all(&line)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Status after the result and the fields its kind of probe reports
+fn add_probe_details(record: &mut Value, stats: &Stats, detail: &ResultDetail, error: Option<&str>) {
+    record["status"] = json!(detail.status.map(|status| status.to_string()));
+    match stats {
+        Stats::Ping(_) => {}
+        Stats::Http(_) => {
+            record["status_code"] = json!(detail.status_code);
+            record["content_size"] = json!(detail.content_size);
+            record["error"] = json!(error);
+        }
+        Stats::Tcp(_) => {
+            record["error"] = json!(error);
+        }
+    }
+}
@@ -0,0 +1,635 @@
+use crate::stats::{ProbeKey, ProbeStatus, StatsMap};
+use chrono::{DateTime, Local, TimeDelta};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Raw samples are kept this long, then only survive in rollups
+const RAW_RETENTION: TimeDelta = TimeDelta::minutes(10);
+
+/// Upper bound on raw samples whatever the probe interval
+const MAX_RAW_SAMPLES: usize = 3600;
+
+/// One-minute rollups cover a full day
+const MINUTE_BUCKETS: usize = 24 * 60;
+
+/// One-hour rollups cover a week
+const HOUR_BUCKETS: usize = 7 * 24;
+
+/// Oldest history any resolution can hold
+pub const HISTORY_SPAN: TimeDelta = TimeDelta::hours(HOUR_BUCKETS as i64);
+
+/// Status changes kept per probe
+const MAX_STATUS_CHANGES: usize = 200;
+
+/// Sample granularity of a stretch of history
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    Raw,
+    Minute,
+    Hour,
+}
+
+/// Aggregate of the samples taken during one minute or hour
+#[derive(Clone, Copy, Debug)]
+pub struct Bucket {
+    pub start: DateTime<Local>,
+    pub samples: u32,
+    pub lost: u32,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    sum: Duration,
+}
+
+impl Bucket {
+    /// A rollup computed elsewhere, e.g. by the database
+    pub fn with_totals(
+        start: DateTime<Local>,
+        samples: u32,
+        lost: u32,
+        min: Option<Duration>,
+        max: Option<Duration>,
+        sum: Duration,
+    ) -> Self {
+        Self {
+            start,
+            samples,
+            lost,
+            min,
+            max,
+            sum,
+        }
+    }
+
+    fn new(start: DateTime<Local>) -> Self {
+        Self {
+            start,
+            samples: 0,
+            lost: 0,
+            min: None,
+            max: None,
+            sum: Duration::ZERO,
+        }
+    }
+
+    fn add(&mut self, sample: Option<Duration>) {
+        self.samples += 1;
+        match sample {
+            Some(d) => {
+                self.sum += d;
+                self.min = Some(self.min.map_or(d, |m| m.min(d)));
+                self.max = Some(self.max.map_or(d, |m| m.max(d)));
+            }
+            None => self.lost += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &Bucket) {
+        self.samples += other.samples;
+        self.lost += other.lost;
+        self.sum += other.sum;
+        self.min = self.min.into_iter().chain(other.min).min();
+        self.max = self.max.into_iter().chain(other.max).max();
+    }
+
+    /// Mean of the samples that got an answer
+    pub fn avg(&self) -> Option<Duration> {
+        let answered = self.samples - self.lost;
+        (answered > 0).then(|| self.sum / answered)
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.lost as f64 / self.samples as f64 * 100.0
+        }
+    }
+}
+
+/// A point of history at some resolution: a raw sample or a bucket average
+#[derive(Clone, Copy, Debug)]
+pub struct HistoryPoint {
+    pub at: DateTime<Local>,
+    /// Latency, or bucket average; `None` when nothing answered
+    pub latency: Option<Duration>,
+    /// Whether any sample in this point was lost
+    pub lost: bool,
+}
+
+/// What a result looked like beyond its latency. Kept with every raw sample,
+/// so it stays small; error text lives in `Timeline` instead.
+#[derive(Clone, Debug, Default)]
+pub struct ResultDetail {
+    /// Probe status right after the result; `None` for restored results
+    pub status: Option<ProbeStatus>,
+    /// Whether the probe counted the result as a success
+    pub success: bool,
+    /// HTTP only
+    pub status_code: Option<u16>,
+    /// HTTP only
+    pub content_size: Option<u64>,
+}
+
+/// One raw result
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// Position among the probe's results, counting from 0; unlike `at` it
+    /// never goes backwards when the wall clock does
+    pub seq: u64,
+    pub at: DateTime<Local>,
+    /// `None` when the result was lost
+    pub latency: Option<Duration>,
+    pub detail: ResultDetail,
+}
+
+/// Multi-resolution history of one probe: raw samples for the last few minutes,
+/// then one-minute rollups for a day and one-hour rollups for a week.
+///
+/// Every level is bounded, so memory stays flat however long the session runs.
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    raw: VecDeque<Sample>,
+    minutes: VecDeque<Bucket>,
+    hours: VecDeque<Bucket>,
+    /// Results ever pushed, i.e. the sequence number of the next one
+    pushed: u64,
+    /// When the first result of this session was pushed
+    live_since: Option<DateTime<Local>>,
+    /// Error text of the raw samples as (first sequence number, error), with an
+    /// entry only where it changed, since failures tend to repeat the same error
+    errors: VecDeque<(u64, Option<String>)>,
+    /// Lost results since the last answer, counted apart from `raw` so long runs aren't capped
+    lost_in_row: usize,
+    /// The same, counting only this session's results
+    lost_in_row_live: usize,
+}
+
+impl Timeline {
+    /// Record a result, `None` when it was lost, with the error it reported
+    pub fn push(
+        &mut self,
+        at: DateTime<Local>,
+        sample: Option<Duration>,
+        detail: ResultDetail,
+        error: Option<&str>,
+    ) {
+        self.live_since.get_or_insert(at);
+        if self.errors.back().and_then(|(_, e)| e.as_deref()) != error {
+            self.errors.push_back((self.pushed, error.map(str::to_string)));
+        }
+        self.add(at, sample, detail, false);
+    }
+
+    /// Record a result from an earlier session
+    pub fn restore(&mut self, at: DateTime<Local>, sample: Option<Duration>, detail: ResultDetail) {
+        if self.errors.back().is_some_and(|(_, e)| e.is_some()) {
+            self.errors.push_back((self.pushed, None));
+        }
+        self.add(at, sample, detail, true);
+    }
+
+    /// Put back a rollup of results from an earlier session that are too old
+    /// to restore one by one, oldest first
+    pub fn restore_bucket(&mut self, resolution: Resolution, bucket: Bucket) {
+        let (buckets, capacity) = match resolution {
+            Resolution::Raw => return,
+            Resolution::Minute => (&mut self.minutes, MINUTE_BUCKETS),
+            Resolution::Hour => (&mut self.hours, HOUR_BUCKETS),
+        };
+        if buckets.len() >= capacity {
+            buckets.pop_front();
+        }
+        buckets.push_back(bucket);
+    }
+
+    fn add(&mut self, at: DateTime<Local>, sample: Option<Duration>, detail: ResultDetail, restored: bool) {
+        self.raw.push_back(Sample {
+            seq: self.pushed,
+            at,
+            latency: sample,
+            detail,
+        });
+        self.pushed += 1;
+        if sample.is_some() {
+            self.lost_in_row = 0;
+            self.lost_in_row_live = 0;
+        } else {
+            self.lost_in_row += 1;
+            self.lost_in_row_live += usize::from(!restored);
+        }
+        while self.raw.len() > MAX_RAW_SAMPLES
+            || self.raw.front().is_some_and(|s| at - s.at > RAW_RETENTION)
+        {
+            self.raw.pop_front();
+        }
+        let oldest = self.raw.front().map_or(self.pushed, |s| s.seq);
+        while self.errors.get(1).is_some_and(|(seq, _)| *seq <= oldest) {
+            self.errors.pop_front();
+        }
+
+        add_to_bucket(&mut self.minutes, MINUTE_BUCKETS, truncate(at, 60), sample);
+        add_to_bucket(&mut self.hours, HOUR_BUCKETS, truncate(at, 3600), sample);
+    }
+
+    /// Start of the raw window ending at `now`, on a minute boundary so the
+    /// one-minute rollups before it are complete
+    pub fn raw_window_start(now: DateTime<Local>) -> DateTime<Local> {
+        truncate(now - RAW_RETENTION, 60)
+    }
+
+    /// Finest resolution that still covers `span`
+    pub fn resolution_for(span: TimeDelta) -> Resolution {
+        if span <= RAW_RETENTION {
+            Resolution::Raw
+        } else if span <= TimeDelta::minutes(MINUTE_BUCKETS as i64) {
+            Resolution::Minute
+        } else {
+            Resolution::Hour
+        }
+    }
+
+    /// History from `cutoff` on at the given resolution, oldest first
+    pub fn since(&self, cutoff: DateTime<Local>, resolution: Resolution) -> Vec<HistoryPoint> {
+        match resolution {
+            Resolution::Raw => {
+                let start = self.raw.partition_point(|s| s.at < cutoff);
+                self.raw
+                    .range(start..)
+                    .map(|s| HistoryPoint {
+                        at: s.at,
+                        latency: s.latency,
+                        lost: s.latency.is_none(),
+                    })
+                    .collect()
+            }
+            Resolution::Minute => bucket_points(&self.minutes, cutoff),
+            Resolution::Hour => bucket_points(&self.hours, cutoff),
+        }
+    }
+
+    /// Aggregate of everything recorded from `cutoff` on, from raw samples while
+    /// they reach back that far and from one-minute rollups beyond
+    pub fn summarize(&self, cutoff: DateTime<Local>) -> Bucket {
+        let mut summary = Bucket::new(cutoff);
+        match Self::resolution_for(Local::now() - cutoff) {
+            Resolution::Raw => {
+                let start = self.raw.partition_point(|s| s.at < cutoff);
+                for sample in self.raw.range(start..) {
+                    summary.add(sample.latency);
+                }
+            }
+            _ => {
+                for bucket in self.minutes.iter().filter(|b| b.start >= cutoff) {
+                    summary.merge(bucket);
+                }
+            }
+        }
+        summary
+    }
+
+    /// Lost results at the end of the history, i.e. failures in a row
+    pub fn trailing_lost(&self) -> usize {
+        self.lost_in_row
+    }
+
+    /// Like `trailing_lost`, counting only results of this session
+    pub fn trailing_lost_live(&self) -> usize {
+        self.lost_in_row_live
+    }
+
+    /// When this session's first result was recorded, if there was one
+    pub fn live_since(&self) -> Option<DateTime<Local>> {
+        self.live_since
+    }
+
+    /// Raw samples from sequence number `seq` on, oldest first
+    pub fn samples_from(&self, seq: u64) -> impl Iterator<Item = &Sample> {
+        let start = self.raw.partition_point(|s| s.seq < seq);
+        self.raw.range(start..)
+    }
+
+    /// Error reported with the raw sample numbered `seq`
+    pub fn error_at(&self, seq: u64) -> Option<&str> {
+        let i = self.errors.partition_point(|(first, _)| *first <= seq);
+        i.checked_sub(1).and_then(|i| self.errors[i].1.as_deref())
+    }
+
+    /// Sequence number the next sample will get
+    pub fn next_seq(&self) -> u64 {
+        self.pushed
+    }
+
+    /// One-minute rollups of the last day, oldest first
+    pub fn minutes(&self) -> impl Iterator<Item = &Bucket> {
+        self.minutes.iter()
+    }
+}
+
+fn add_to_bucket(
+    buckets: &mut VecDeque<Bucket>,
+    capacity: usize,
+    start: DateTime<Local>,
+    sample: Option<Duration>,
+) {
+    if buckets.back().is_none_or(|b| b.start != start) {
+        if buckets.len() >= capacity {
+            buckets.pop_front();
+        }
+        buckets.push_back(Bucket::new(start));
+    }
+    if let Some(bucket) = buckets.back_mut() {
+        bucket.add(sample);
+    }
+}
+
+fn bucket_points(buckets: &VecDeque<Bucket>, cutoff: DateTime<Local>) -> Vec<HistoryPoint> {
+    buckets
+        .iter()
+        .filter(|b| b.start >= cutoff)
+        .map(|b| HistoryPoint {
+            at: b.start,
+            latency: b.avg(),
+            lost: b.lost > 0,
+        })
+        .collect()
+}
+
+/// Start of the `secs`-long period containing `at`
+fn truncate(at: DateTime<Local>, secs: i64) -> DateTime<Local> {
+    let offset = at.timestamp().rem_euclid(secs);
+    at - TimeDelta::seconds(offset) - TimeDelta::nanoseconds(at.timestamp_subsec_nanos() as i64)
+}
+
+/// A probe moving from one status to another
+#[derive(Clone, Debug)]
+pub struct StatusChange {
+    pub at: DateTime<Local>,
+    pub from: String,
+    pub to: String,
+    /// Time spent in `from`, unknown for the first change
+    pub duration: Option<Duration>,
+}
+
+/// Most recent status changes of one probe, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct StatusLog {
+    changes: VecDeque<StatusChange>,
+    /// When the current status was entered
+    entered: Option<DateTime<Local>>,
+    /// Changes ever recorded or restored, including ones since dropped
+    total: u64,
+}
+
+impl StatusLog {
+    /// Record a change if the status actually moved
+    pub fn record<S: Debug + PartialEq>(&mut self, at: DateTime<Local>, from: &S, to: &S) {
+        if from == to {
+            return;
+        }
+        self.push(StatusChange {
+            at,
+            from: format!("{:?}", from),
+            to: format!("{:?}", to),
+            duration: self.entered.and_then(|entered| (at - entered).to_std().ok()),
+        });
+        self.entered = Some(at);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &StatusChange> {
+        self.changes.iter()
+    }
+
+    /// Put back a change from an earlier session. The current status's start is
+    /// left unknown, since the probe starts over from `NotStarted`.
+    pub fn restore(&mut self, change: StatusChange) {
+        self.push(change);
+    }
+
+    /// Changes recorded after the first `seen`, with the new total to pass next time
+    pub fn since(&self, seen: u64) -> (impl Iterator<Item = &StatusChange>, u64) {
+        let new = (self.total - seen.min(self.total)).min(self.changes.len() as u64) as usize;
+        (self.changes.iter().skip(self.changes.len() - new), self.total)
+    }
+
+    fn push(&mut self, change: StatusChange) {
+        if self.changes.len() >= MAX_STATUS_CHANGES {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+        self.total += 1;
+    }
+}
+
+/// Tracks which results and status changes of each probe have already been
+/// handed on, for consumers that poll the stats map (headless output, the database).
+///
+/// Positions are counted per probe rather than taken from timestamps, so a
+/// wall clock stepping backwards doesn't hide results recorded after it.
+pub struct HistoryCursor {
+    /// Next sample sequence number to hand on
+    results: HashMap<ProbeKey, u64>,
+    /// Status changes already handed on
+    changes: HashMap<ProbeKey, u64>,
+}
+
+impl HistoryCursor {
+    /// Start after everything already in `stats`; probes added later are
+    /// followed from their first result
+    pub fn new(stats: &StatsMap) -> Self {
+        let stats_lock = stats.read();
+        Self {
+            results: stats_lock
+                .iter()
+                .map(|(&key, stats)| (key, stats.timeline().next_seq()))
+                .collect(),
+            changes: stats_lock
+                .iter()
+                .map(|(&key, stats)| (key, stats.status_log().since(0).1))
+                .collect(),
+        }
+    }
+
+    /// Raw results recorded since the last call for this probe, oldest first
+    pub fn new_results(&mut self, key: ProbeKey, timeline: &Timeline) -> Vec<Sample> {
+        let next = self.results.entry(key).or_insert(0);
+        let samples: Vec<Sample> = timeline.samples_from(*next).cloned().collect();
+        *next = timeline.next_seq();
+        samples
+    }
+
+    /// Status changes recorded since the last call for this probe, oldest first
+    pub fn new_changes(&mut self, key: ProbeKey, log: &StatusLog) -> Vec<StatusChange> {
+        let seen = self.changes.entry(key).or_insert(0);
+        let (changes, total) = log.since(*seen);
+        let changes = changes.cloned().collect();
+        *seen = total;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn raw_samples_roll_up_into_minutes_and_hours() {
+        let base = truncate(Local::now() - TimeDelta::hours(2), 3600);
+        let mut timeline = Timeline::default();
+        // Every 10s for half an hour, every third one lost
+        for i in 0..180 {
+            let sample = if i % 3 == 2 { None } else { ms(10 + i % 3) };
+            timeline.push(base + TimeDelta::seconds(i as i64 * 10), sample, ResultDetail::default(), None);
+        }
+
+        // Raw samples only reach back ten minutes from the newest one
+        let last = base + TimeDelta::seconds(1790);
+        let raw = timeline.since(base, Resolution::Raw);
+        assert_eq!(raw.len(), 61);
+        assert_eq!(raw.first().map(|p| p.at), Some(last - RAW_RETENTION));
+
+        let minutes: Vec<&Bucket> = timeline.minutes().collect();
+        assert_eq!(minutes.len(), 30);
+        for (i, bucket) in minutes.iter().enumerate() {
+            assert_eq!(bucket.start, base + TimeDelta::minutes(i as i64));
+            assert_eq!(bucket.samples, 6);
+            assert_eq!(bucket.lost, 2);
+            assert_eq!(bucket.min, ms(10));
+            assert_eq!(bucket.max, ms(11));
+            assert_eq!(bucket.avg(), Some(Duration::from_micros(10_500)));
+        }
+
+        let hours = timeline.since(base, Resolution::Hour);
+        assert_eq!(hours.len(), 1);
+        assert_eq!(hours[0].at, base);
+        assert!(hours[0].lost);
+        assert_eq!(hours[0].latency, Some(Duration::from_micros(10_500)));
+        assert_eq!(timeline.hours[0].samples, 180);
+    }
+
+    #[test]
+    fn summarize_counts_raw_samples_exactly_and_only_whole_minutes_beyond() {
+        let end = truncate(Local::now(), 60);
+        let mut timeline = Timeline::default();
+        // Every 30s for the 20 minutes before `end`
+        for i in 0..40 {
+            timeline.push(end - TimeDelta::minutes(20) + TimeDelta::seconds(i * 30), ms(5), ResultDetail::default(), None);
+        }
+
+        // Within the raw window the cutoff splits a minute
+        let recent = timeline.summarize(end - TimeDelta::minutes(5) + TimeDelta::seconds(1));
+        assert_eq!(recent.samples, 9);
+
+        // Beyond it only minutes starting after the cutoff count
+        let older = timeline.summarize(end - TimeDelta::minutes(15) + TimeDelta::seconds(1));
+        assert_eq!(older.samples, 28);
+        assert_eq!(older.avg(), ms(5));
+    }
+
+    #[test]
+    fn every_level_stays_within_capacity() {
+        let base = Local::now() - TimeDelta::days(9);
+        let mut timeline = Timeline::default();
+        // One sample a minute for eight days
+        for i in 0..8 * 24 * 60 {
+            timeline.push(base + TimeDelta::minutes(i), ms(1), ResultDetail::default(), None);
+        }
+        assert_eq!(timeline.minutes.len(), MINUTE_BUCKETS);
+        assert_eq!(timeline.hours.len(), HOUR_BUCKETS);
+        assert_eq!(timeline.raw.len(), 11);
+
+        // A burst within one instant is capped by count instead of age
+        let at = Local::now();
+        for _ in 0..MAX_RAW_SAMPLES + 400 {
+            timeline.push(at, None, ResultDetail::default(), None);
+        }
+        assert_eq!(timeline.raw.len(), MAX_RAW_SAMPLES);
+        // Failures in a row are counted past what the raw samples hold
+        assert_eq!(timeline.trailing_lost(), MAX_RAW_SAMPLES + 400);
+        assert_eq!(timeline.trailing_lost_live(), MAX_RAW_SAMPLES + 400);
+        timeline.push(at, ms(1), ResultDetail::default(), None);
+        assert_eq!(timeline.trailing_lost(), 0);
+    }
+
+    #[test]
+    fn errors_are_stored_only_where_they_change() {
+        let at = Local::now();
+        let mut timeline = Timeline::default();
+        for error in [None, Some("refused"), Some("refused"), Some("timeout"), None, None] {
+            timeline.push(at, None, ResultDetail::default(), error);
+        }
+        let errors: Vec<Option<&str>> = (0..6).map(|seq| timeline.error_at(seq)).collect();
+        assert_eq!(errors, [None, Some("refused"), Some("refused"), Some("timeout"), None, None]);
+        assert_eq!(timeline.errors.len(), 3);
+
+        // Entries for samples that aged out are dropped with them
+        for _ in 0..MAX_RAW_SAMPLES {
+            timeline.push(at, None, ResultDetail::default(), Some("timeout"));
+        }
+        assert_eq!(timeline.errors.len(), 1);
+        assert_eq!(timeline.error_at(timeline.next_seq() - 1), Some("timeout"));
+    }
+
+    #[test]
+    fn restored_results_are_not_live() {
+        let now = Local::now();
+        let mut timeline = Timeline::default();
+        for i in 0..5 {
+            timeline.restore(now - TimeDelta::seconds(10 - i), None, ResultDetail::default());
+        }
+        assert_eq!(timeline.trailing_lost(), 5);
+        assert_eq!(timeline.trailing_lost_live(), 0);
+        assert_eq!(timeline.live_since(), None);
+
+        timeline.push(now, None, ResultDetail::default(), None);
+        assert_eq!(timeline.trailing_lost_live(), 1);
+        assert_eq!(timeline.live_since(), Some(now));
+    }
+
+    #[test]
+    fn status_log_keeps_the_newest_changes() {
+        let mut log = StatusLog::default();
+        let at = Local::now();
+        for i in 0..MAX_STATUS_CHANGES + 10 {
+            log.record(at, &(i % 2), &((i + 1) % 2));
+        }
+        assert_eq!(log.iter().count(), MAX_STATUS_CHANGES);
+
+        let (changes, total) = log.since(MAX_STATUS_CHANGES as u64 + 7);
+        assert_eq!(changes.count(), 3);
+        assert_eq!(total, MAX_STATUS_CHANGES as u64 + 10);
+    }
+
+    #[test]
+    fn cursor_follows_sequence_numbers_across_clock_jumps() {
+        let stats = StatsMap::default();
+        let mut cursor = HistoryCursor::new(&stats);
+        let key = ("127.0.0.1".parse().unwrap(), crate::stats::Probe::Icmp);
+        let mut timeline = Timeline::default();
+        let now = Local::now();
+
+        timeline.push(now, ms(1), ResultDetail::default(), None);
+        timeline.push(now + TimeDelta::seconds(1), ms(2), ResultDetail::default(), None);
+        let first = cursor.new_results(key, &timeline);
+        assert_eq!(first.iter().map(|s| s.seq).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(cursor.new_results(key, &timeline).is_empty());
+
+        // The clock steps back a minute; the result is still new
+        timeline.push(now - TimeDelta::minutes(1), ms(3), ResultDetail::default(), None);
+        let after_jump = cursor.new_results(key, &timeline);
+        assert_eq!(after_jump.len(), 1);
+        assert_eq!(after_jump[0].seq, 2);
+        assert_eq!(after_jump[0].latency, ms(3));
+
+        let mut log = StatusLog::default();
+        log.record(now, &1, &2);
+        assert_eq!(cursor.new_changes(key, &log).len(), 1);
+        log.record(now - TimeDelta::minutes(1), &2, &1);
+        assert_eq!(cursor.new_changes(key, &log).len(), 1);
+        assert!(cursor.new_changes(key, &log).is_empty());
+    }
+}
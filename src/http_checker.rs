@@ -1,65 +1,129 @@
-use crate::stats::Stats;
-use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::net::IpAddr;
+use crate::config::{BodyMatch, HttpMethod, ProbeConfig};
+use crate::http_stats::{CertInfo, HttpResult, HttpTimings};
+use crate::messages::MessageSender;
+use crate::stats::{Probe, Stats, StatsMap};
+use chrono::DateTime;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::client::conn::http1;
+use hyper::header::HOST;
+use hyper::{Method, Request, Response};
+use hyper_util::rt::TokioIo;
+use parking_lot::Mutex;
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::client::WebPkiServerVerifier;
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio_rustls::TlsConnector;
 
-/// Start an async HTTP checking task for a specific IP address
+/// Where and how one HTTP(S) probe connects, fixed for the life of the task
+struct Target {
+    addr: SocketAddr,
+    /// Host header value: the SNI name if set, otherwise the address
+    host: String,
+    /// TLS connector, server name and presented certificate for HTTPS probes
+    tls: Option<(TlsConnector, ServerName<'static>, PeerCert)>,
+}
+
+/// Certificate the server presented in the latest handshake, set by the
+/// verifier so it is known even when verification rejects it
+type PeerCert = Arc<Mutex<Option<CertificateDer<'static>>>>;
+
+/// Start an async HTTP(S) checking task for a specific IP address.
+///
+/// Each check opens a fresh connection so the TCP connect, TLS handshake,
+/// time to first byte and body transfer can be timed separately.
 pub async fn start_http_task(
     ip: IpAddr,
-    port: u16,
-    stats: Arc<RwLock<HashMap<IpAddr, Stats>>>,
+    probe: Probe,
+    config: ProbeConfig,
+    stats: StatsMap,
+    messages: MessageSender,
     mut shutdown: tokio::sync::watch::Receiver<bool>,
 ) {
-    // Create HTTP client with timeout
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .redirect(reqwest::redirect::Policy::none()) // Don't follow redirects
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Failed to create HTTP client for {}: {}", ip, e);
-            return;
+    let (port, tls) = match probe {
+        Probe::Http(port) => (port, false),
+        Probe::Https(port) => (port, true),
+        _ => return,
+    };
+    let addr = SocketAddr::new(ip, port);
+
+    // With an SNI name, request by hostname but connect to this host's IP
+    let default_port = if tls { 443 } else { 80 };
+    let host = match (&config.sni, port == default_port) {
+        (Some(name), true) => name.clone(),
+        (Some(name), false) => format!("{}:{}", name, port),
+        (None, true) if ip.is_ipv4() => ip.to_string(),
+        (None, true) => format!("[{}]", ip),
+        // SocketAddr brackets IPv6 literals for us
+        (None, false) => addr.to_string(),
+    };
+
+    let tls = if tls {
+        let server_name = match &config.sni {
+            Some(name) => match ServerName::try_from(name.clone()) {
+                Ok(name) => name,
+                Err(e) => {
+                    messages.error(format!("Invalid SNI name {}: {}", name, e));
+                    return;
+                }
+            },
+            None => ServerName::IpAddress(ip.into()),
+        };
+        match tls_connector(config.accept_invalid_certs) {
+            Ok((connector, peer_cert)) => Some((connector, server_name, peer_cert)),
+            Err(e) => {
+                messages.error(format!("Failed to create TLS client for {}: {}", ip, e));
+                return;
+            }
         }
+    } else {
+        None
+    };
+
+    let target = Target { addr, host, tls };
+
+    // Patterns are validated when parsed, so this only fails on a bug
+    let body_regex = match &config.expect_body {
+        Some(BodyMatch::Regex(pattern)) => Regex::new(pattern).ok(),
+        _ => None,
     };
 
-    let url = format!("http://{}:{}", ip, port);
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut interval = tokio::time::interval(config.interval);
 
     loop {
         tokio::select! {
             _ = interval.tick() => {
-                // Measure request time
                 let start = Instant::now();
-                let result = client.get(&url).send().await;
-                let duration = start.elapsed();
-
-                // Extract stats from response
-                let (status_code, content_size, error) = match result {
-                    Ok(resp) => {
-                        let status = resp.status().as_u16();
-                        let size = resp.content_length();
-                        (Some(status), size, None)
-                    }
-                    Err(e) => {
-                        let error_msg = if e.is_timeout() {
-                            "Request timeout".to_string()
-                        } else if e.is_connect() {
-                            "Connection refused".to_string()
-                        } else {
-                            format!("{}", e)
-                        };
-                        (None, None, Some(error_msg))
-                    }
+                let result = match tokio::time::timeout(
+                    config.timeout,
+                    check(&target, &config, body_regex.as_ref()),
+                )
+                .await
+                {
+                    Ok(result) => result,
+                    Err(_) => HttpResult::failed(start.elapsed(), "Request timeout".to_string()),
                 };
 
                 // Update statistics
                 {
                     let mut stats_lock = stats.write();
-                    if let Some(Stats::Http(host_stats)) = stats_lock.get_mut(&ip) {
-                        host_stats.update(status_code, duration, content_size, error);
+                    if let Some(Stats::Http(host_stats)) = stats_lock.get_mut(&(ip, probe)) {
+                        host_stats.update(result);
                     }
                 }
             }
@@ -70,3 +134,530 @@ pub async fn start_http_task(
         }
     }
 }
+
+/// Run one request on a new connection, timing each phase
+async fn check(target: &Target, config: &ProbeConfig, body_regex: Option<&Regex>) -> HttpResult {
+    let request = match build_request(target, config) {
+        Ok(request) => request,
+        Err(e) => return HttpResult::failed(Duration::ZERO, format!("Invalid request: {}", e)),
+    };
+
+    let start = Instant::now();
+    let stream = match TcpStream::connect(target.addr).await {
+        Ok(stream) => stream,
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            return HttpResult::failed(start.elapsed(), "Connection refused".to_string())
+        }
+        Err(e) => return HttpResult::failed(start.elapsed(), describe_error(&e)),
+    };
+    let connect = start.elapsed();
+
+    match &target.tls {
+        Some((connector, server_name, peer_cert)) => {
+            let tls_start = Instant::now();
+            peer_cert.lock().take();
+            let stream = match connector.connect(server_name.clone(), stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    // Still show which certificate was rejected, e.g. how long ago it expired
+                    let mut result =
+                        HttpResult::failed(start.elapsed(), format!("TLS: {}", describe_error(&e)));
+                    result.cert = peer_cert.lock().take().and_then(|der| parse_certificate(&der));
+                    return result;
+                }
+            };
+            let tls = tls_start.elapsed();
+
+            // Resumed sessions skip verification, so read the certificate from the session
+            let cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|der| parse_certificate(der));
+
+            let mut result =
+                exchange(stream, request, config, body_regex, start, connect, Some(tls)).await;
+            result.cert = cert;
+            result
+        }
+        None => exchange(stream, request, config, body_regex, start, connect, None).await,
+    }
+}
+
+/// Send the request over an established connection and read the response
+async fn exchange<S>(
+    stream: S,
+    request: Request<Full<Bytes>>,
+    config: &ProbeConfig,
+    body_regex: Option<&Regex>,
+    start: Instant,
+    connect: Duration,
+    tls: Option<Duration>,
+) -> HttpResult
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = match http1::handshake(TokioIo::new(stream)).await {
+        Ok(parts) => parts,
+        Err(e) => return HttpResult::failed(start.elapsed(), describe_error(&e)),
+    };
+    // Drive the connection until we're done with it; it is never reused
+    let _connection = AbortOnDrop(tokio::spawn(connection));
+
+    let sent = Instant::now();
+    match sender.send_request(request).await {
+        Ok(resp) => {
+            let ttfb = sent.elapsed();
+            let response_time = start.elapsed();
+            let status_code = resp.status().as_u16();
+
+            let keep_body = config.read_body || config.expect_body.is_some();
+            let received = Instant::now();
+            let (body, error) = match read_body(resp, config.max_body_size, keep_body).await {
+                Ok(body) => (Some(body), None),
+                Err(e) => (None, Some(format!("Body: {}", describe_error(&e)))),
+            };
+
+            let failed_assertion = check_response(
+                status_code,
+                response_time,
+                body.as_ref(),
+                config,
+                body_regex,
+            );
+
+            HttpResult {
+                status_code: Some(status_code),
+                success: config.is_success(status_code)
+                    && failed_assertion.is_none()
+                    && error.is_none(),
+                response_time,
+                timings: Some(HttpTimings {
+                    connect,
+                    tls,
+                    ttfb,
+                    transfer: received.elapsed(),
+                    total: start.elapsed(),
+                }),
+                content_size: body.as_ref().map(|b| b.size),
+                content_hash: body.as_ref().filter(|_| config.read_body).map(BodySample::hash),
+                content_truncated: body.as_ref().is_some_and(|b| b.truncated),
+                error,
+                failed_assertion,
+                cert: None,
+            }
+        }
+        Err(e) => HttpResult::failed(start.elapsed(), describe_error(&e)),
+    }
+}
+
+/// Aborts the task when dropped, including when a timeout drops the future holding it
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Build the request for one check; fails if a configured header is invalid
+fn build_request(target: &Target, config: &ProbeConfig) -> Result<Request<Full<Bytes>>, hyper::http::Error> {
+    let method = match config.method {
+        HttpMethod::Get => Method::GET,
+        HttpMethod::Head => Method::HEAD,
+        HttpMethod::Post => Method::POST,
+    };
+
+    let mut builder = Request::builder().method(method).uri(config.path.as_str());
+
+    // A configured Host header replaces the default one
+    if !config
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("host"))
+    {
+        builder = builder.header(HOST, target.host.as_str());
+    }
+    for (name, value) in &config.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+
+    let body = config.body.clone().map(Bytes::from).unwrap_or_default();
+    builder.body(Full::new(body))
+}
+
+/// Response body read up to the size cap
+struct BodySample {
+    /// Bytes received, up to the cap
+    size: u64,
+    /// Body contents, only kept when hashed or asserted on
+    bytes: Vec<u8>,
+    /// The body was longer than the cap and the rest was not read
+    truncated: bool,
+}
+
+impl BodySample {
+    /// Fingerprint for change detection (not cryptographic)
+    fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Stream the body frame by frame, stopping once `cap` bytes have been read
+async fn read_body(
+    resp: Response<Incoming>,
+    cap: usize,
+    keep: bool,
+) -> Result<BodySample, hyper::Error> {
+    let mut body = resp.into_body();
+    let mut sample = BodySample {
+        size: 0,
+        bytes: Vec::new(),
+        truncated: false,
+    };
+
+    while let Some(frame) = body.frame().await {
+        // Trailers carry no body data
+        let Ok(chunk) = frame?.into_data() else {
+            continue;
+        };
+
+        let room = cap - sample.size as usize;
+        let take = chunk.len().min(room);
+        if keep {
+            sample.bytes.extend_from_slice(&chunk[..take]);
+        }
+        sample.size += take as u64;
+
+        if chunk.len() > room {
+            sample.truncated = true;
+            break;
+        }
+    }
+
+    Ok(sample)
+}
+
+/// Check a response against the configured assertions, returning the first failure
+fn check_response(
+    code: u16,
+    response_time: Duration,
+    body: Option<&BodySample>,
+    config: &ProbeConfig,
+    body_regex: Option<&Regex>,
+) -> Option<String> {
+    if !config.status_expected(code) {
+        return Some(format!("Unexpected status {}", code));
+    }
+
+    if let Some(max) = config.max_response_time {
+        if response_time > max {
+            return Some(format!(
+                "Slow: {}ms > {}ms",
+                response_time.as_millis(),
+                max.as_millis()
+            ));
+        }
+    }
+
+    let expected = config.expect_body.as_ref()?;
+    // A failed body read is reported as an error instead
+    let body = String::from_utf8_lossy(&body?.bytes);
+
+    match expected {
+        BodyMatch::Contains(text) if !body.contains(text.as_str()) => {
+            Some(format!("Body lacks \"{}\"", text))
+        }
+        BodyMatch::Regex(pattern) if !body_regex.is_some_and(|re| re.is_match(&body)) => {
+            Some(format!("Body !~ /{}/", pattern))
+        }
+        _ => None,
+    }
+}
+
+/// Short error text from the innermost cause, which holds the useful detail
+/// (e.g. "invalid peer certificate: Expired")
+fn describe_error(e: &(dyn Error + 'static)) -> String {
+    let mut root = e;
+    while let Some(source) = root.source() {
+        root = source;
+    }
+    root.to_string()
+}
+
+/// TLS client matching the old reqwest setup: webpki roots, HTTP/1.1 only.
+/// Also returns where the verifier leaves each presented certificate.
+fn tls_connector(
+    accept_invalid_certs: bool,
+) -> Result<(TlsConnector, PeerCert), tokio_rustls::rustls::Error> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let verifier: Arc<dyn ServerCertVerifier> = if accept_invalid_certs {
+        Arc::new(AcceptAnyCert(Arc::clone(&provider)))
+    } else {
+        let roots: RootCertStore = webpki_roots::TLS_SERVER_ROOTS.iter().cloned().collect();
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(&provider))
+            .build()
+            .map_err(|e| tokio_rustls::rustls::Error::General(e.to_string()))?
+    };
+    let peer_cert = PeerCert::default();
+
+    let mut config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(RecordingVerifier {
+            inner: verifier,
+            seen: Arc::clone(&peer_cert),
+        }))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok((TlsConnector::from(Arc::new(config)), peer_cert))
+}
+
+/// Keeps the server's certificate in `seen`, then leaves the decision to `inner`
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    seen: PeerCert,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        *self.seen.lock() = Some(end_entity.clone().into_owned());
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Certificate verifier for `--insecure`: accepts any certificate but still
+/// checks handshake signatures so the connection itself is sound
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Extract subject, issuer and expiry from a DER-encoded certificate
+fn parse_certificate(der: &[u8]) -> Option<CertInfo> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let not_after = DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)?;
+
+    Some(CertInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        not_after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::AppMode;
+    use chrono::{Datelike, TimeDelta, Utc};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    /// Self-signed certificate for "pinger test" valid until `days` from now
+    fn certificate(days: i64) -> (CertificateDer<'static>, PrivateKeyDer<'static>) {
+        let date = |offset: i64| {
+            let day = (Utc::now() + TimeDelta::days(offset)).date_naive();
+            rcgen::date_time_ymd(day.year(), day.month() as u8, day.day() as u8)
+        };
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.distinguished_name.push(rcgen::DnType::CommonName, "pinger test");
+        params.not_before = date(days - 60);
+        params.not_after = date(days);
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        (cert.der().clone(), key)
+    }
+
+    /// HTTPS server on a loopback port answering every request with 200
+    async fn serve_https(days: i64) -> SocketAddr {
+        let (cert, key) = certificate(days);
+        let config = ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // Fails when the client rejects the certificate
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let response = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn check_https(addr: SocketAddr, insecure: bool) -> HttpResult {
+        let (connector, peer_cert) = tls_connector(insecure).unwrap();
+        let target = Target {
+            addr,
+            host: addr.to_string(),
+            tls: Some((connector, ServerName::IpAddress(addr.ip().into()), peer_cert)),
+        };
+        check(&target, &ProbeConfig::defaults(AppMode::Http), None).await
+    }
+
+    #[tokio::test]
+    async fn self_signed_certificates_need_insecure() {
+        let addr = serve_https(30).await;
+
+        let rejected = check_https(addr, false).await;
+        assert_eq!(rejected.status_code, None);
+        let error = rejected.error.as_deref().unwrap_or_default();
+        assert!(error.starts_with("TLS: invalid peer certificate"), "{}", error);
+
+        let accepted = check_https(addr, true).await;
+        assert_eq!(accepted.status_code, Some(200));
+        assert!(accepted.success);
+        assert!(accepted.timings.is_some_and(|t| t.tls.is_some()));
+
+        for result in [rejected, accepted] {
+            let cert = result.cert.expect("certificate details");
+            assert_eq!(cert.subject, "CN=pinger test");
+            assert_eq!(cert.issuer, "CN=pinger test");
+            assert!((28..=30).contains(&cert.days_until_expiry()), "{:?}", cert);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_expired_certificates_still_report_expiry() {
+        let addr = serve_https(-10).await;
+
+        let result = check_https(addr, false).await;
+        assert_eq!(result.status_code, None);
+        let cert = result.cert.expect("certificate details");
+        assert!((-11..=-9).contains(&cert.days_until_expiry()), "{:?}", cert);
+
+        // --insecure accepts it anyway
+        assert_eq!(check_https(addr, true).await.status_code, Some(200));
+    }
+
+    #[tokio::test]
+    async fn timed_out_checks_stop_sending() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let target = Target {
+            addr,
+            host: addr.to_string(),
+            tls: None,
+        };
+        // A body far bigger than the socket buffers, to a server not reading yet
+        let body_size = 64 << 20;
+        let config = ProbeConfig {
+            method: HttpMethod::Post,
+            body: Some("x".repeat(body_size)),
+            ..ProbeConfig::defaults(AppMode::Http)
+        };
+
+        let check = tokio::time::timeout(Duration::from_millis(200), check(&target, &config, None));
+        let (result, accepted) = tokio::join!(check, listener.accept());
+        assert!(result.is_err());
+
+        // Once the check timed out, the rest of the body must not follow
+        let (mut socket, _) = accepted.unwrap();
+        let mut received = 0;
+        let mut buf = vec![0; 1 << 16];
+        let drained = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => received += n,
+                }
+            }
+        })
+        .await;
+        assert!(drained.is_ok(), "connection left open");
+        assert!(received < body_size, "whole body sent after the timeout");
+    }
+}
@@ -1,16 +1,88 @@
-use chrono::{DateTime, Local};
-use std::collections::VecDeque;
+use crate::history::{Bucket, Resolution, ResultDetail, StatusChange, StatusLog, Timeline};
+use crate::latency::{LatencyHistogram, LatencySpread, LatencyWindow};
+use crate::stats::ProbeStatus;
+use chrono::{DateTime, Local, Utc};
+use std::collections::{BTreeMap, VecDeque};
 use std::time::Duration;
 
 const MAX_SAMPLES: usize = 100;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum HttpStatus {
     NotStarted,
-    Success,       // 2xx responses
-    ClientError,   // 4xx responses
-    ServerError,   // 5xx responses
-    NetworkError,  // Connection/timeout errors
+    Success,         // Responses matching the success codes (default 2xx/3xx)
+    ClientError,     // Other 4xx responses
+    ServerError,     // Other 5xx responses
+    NetworkError,    // Connection/timeout errors
+    AssertionFailed, // Response received but failed an expected status/body/time check
+}
+
+/// Peer certificate details from an HTTPS response
+#[derive(Clone, Debug)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: DateTime<Utc>,
+}
+
+impl CertInfo {
+    /// Whole days until the certificate expires (negative once expired)
+    pub fn days_until_expiry(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+}
+
+/// Where the time of one HTTP check went
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpTimings {
+    /// TCP handshake
+    pub connect: Duration,
+    /// TLS handshake (HTTPS only)
+    pub tls: Option<Duration>,
+    /// Request sent to response headers received
+    pub ttfb: Duration,
+    /// Response headers to end of body
+    pub transfer: Duration,
+    /// Start of connect to end of body
+    pub total: Duration,
+}
+
+/// Outcome of a single HTTP check
+#[derive(Clone, Debug)]
+pub struct HttpResult {
+    pub status_code: Option<u16>,
+    /// Whether the response matched the success predicate and passed all assertions
+    pub success: bool,
+    pub response_time: Duration,
+    /// Phase timings, when a response was received
+    pub timings: Option<HttpTimings>,
+    pub content_size: Option<u64>,
+    /// Hash of the body, when it was read
+    pub content_hash: Option<u64>,
+    /// The body hit the size cap, so `content_size` is a lower bound
+    pub content_truncated: bool,
+    pub error: Option<String>,
+    /// First failed response assertion, if any
+    pub failed_assertion: Option<String>,
+    pub cert: Option<CertInfo>,
+}
+
+impl HttpResult {
+    /// A check that got no response
+    pub fn failed(response_time: Duration, error: String) -> Self {
+        Self {
+            status_code: None,
+            success: false,
+            response_time,
+            timings: None,
+            content_size: None,
+            content_hash: None,
+            content_truncated: false,
+            error: Some(error),
+            failed_assertion: None,
+            cert: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -20,14 +92,40 @@ pub struct HttpStats {
     pub avg_response_time: Option<Duration>,
     pub min_response_time: Option<Duration>,
     pub max_response_time: Option<Duration>,
+    /// Percentiles, standard deviation and jitter of recent response times
+    pub spread: Option<LatencySpread>,
     pub last_status_code: Option<u16>,
+    pub last_timings: Option<HttpTimings>,
+    /// Average of each phase over the recent responses
+    pub avg_timings: Option<HttpTimings>,
     pub last_content_size: Option<u64>,
+    pub content_truncated: bool,
+    /// Hash of the most recently read body
+    pub content_hash: Option<u64>,
+    /// Times the body hash differed from the previous check
+    pub content_changes: u64,
+    pub last_content_change: Option<DateTime<Local>>,
     pub last_error: Option<String>,
+    /// Most recent peer certificate seen (HTTPS only)
+    pub cert: Option<CertInfo>,
     pub requests_sent: u64,
     pub requests_successful: u64,
+    /// Requests that got no response (connect errors, timeouts)
+    pub network_errors: u64,
+    /// Responses per status class, indexed 0 for 1xx through 4 for 5xx
+    pub class_counts: [u64; 5],
+    /// Responses per distinct status code over the session
+    pub status_counts: BTreeMap<u16, u64>,
     pub success_rate_percent: f64,
+    /// Response times of every response this session
+    pub response_histogram: LatencyHistogram,
     pub last_updated: DateTime<Local>,
-    recent_times: VecDeque<Duration>,
+    recent_times: LatencyWindow,
+    /// Recent response times in order, `None` when no response arrived
+    recent_results: VecDeque<Option<Duration>>,
+    recent_timings: VecDeque<HttpTimings>,
+    timeline: Timeline,
+    status_log: StatusLog,
 }
 
 impl Default for HttpStats {
@@ -38,14 +136,30 @@ impl Default for HttpStats {
             avg_response_time: None,
             min_response_time: None,
             max_response_time: None,
+            spread: None,
             last_status_code: None,
+            last_timings: None,
+            avg_timings: None,
             last_content_size: None,
+            content_truncated: false,
+            content_hash: None,
+            content_changes: 0,
+            last_content_change: None,
             last_error: None,
+            cert: None,
             requests_sent: 0,
             requests_successful: 0,
+            network_errors: 0,
+            class_counts: [0; 5],
+            status_counts: BTreeMap::new(),
             success_rate_percent: 0.0,
+            response_histogram: LatencyHistogram::default(),
             last_updated: Local::now(),
-            recent_times: VecDeque::with_capacity(MAX_SAMPLES),
+            recent_times: LatencyWindow::new(MAX_SAMPLES),
+            recent_results: VecDeque::with_capacity(MAX_SAMPLES),
+            recent_timings: VecDeque::with_capacity(MAX_SAMPLES),
+            timeline: Timeline::default(),
+            status_log: StatusLog::default(),
         }
     }
 }
@@ -56,43 +170,84 @@ impl HttpStats {
     }
 
     /// Update statistics with a new HTTP result
-    pub fn update(
-        &mut self,
-        status_code: Option<u16>,
-        response_time: Duration,
-        content_size: Option<u64>,
-        error: Option<String>,
-    ) {
+    pub fn update(&mut self, result: HttpResult) {
+        let HttpResult {
+            status_code,
+            success,
+            response_time,
+            timings,
+            content_size,
+            content_hash,
+            content_truncated,
+            error,
+            failed_assertion,
+            cert,
+        } = result;
+
+        let previous = self.status;
         self.requests_sent += 1;
+        if success {
+            self.requests_successful += 1;
+        }
         self.last_updated = Local::now();
         self.last_response_time = Some(response_time);
+
+        if self.recent_results.len() >= MAX_SAMPLES {
+            self.recent_results.pop_front();
+        }
+        self.recent_results.push_back(status_code.map(|_| response_time));
+        self.last_timings = timings;
+        if let Some(timings) = timings {
+            if self.recent_timings.len() >= MAX_SAMPLES {
+                self.recent_timings.pop_front();
+            }
+            self.recent_timings.push_back(timings);
+            self.calculate_timings();
+        }
         self.last_content_size = content_size;
-        self.last_error = error.clone();
+        // A response whose body couldn't be read
+        let body_failed = status_code.is_some() && error.is_some();
+        self.content_truncated = content_truncated;
+        // Compare against the last body seen, skipping checks that didn't read one
+        if let Some(hash) = content_hash {
+            if self.content_hash.is_some_and(|previous| previous != hash) {
+                self.content_changes += 1;
+                self.last_content_change = Some(self.last_updated);
+            }
+            self.content_hash = Some(hash);
+        }
+        self.last_error = error.or_else(|| failed_assertion.clone());
+        // Keep the last known certificate through failed requests
+        if cert.is_some() {
+            self.cert = cert;
+        }
 
         match status_code {
             Some(code) => {
                 self.last_status_code = Some(code);
-                self.requests_successful += 1;
+                self.count_status(code, 1);
 
-                // Determine status based on code
+                // Status follows the same success decision as the counters;
+                // the code class only says what kind of failure it was
                 self.status = match code {
-                    200..=299 => HttpStatus::Success,
+                    _ if success => HttpStatus::Success,
+                    _ if failed_assertion.is_some() => HttpStatus::AssertionFailed,
+                    _ if body_failed => HttpStatus::NetworkError,
                     400..=499 => HttpStatus::ClientError,
                     500..=599 => HttpStatus::ServerError,
-                    _ => HttpStatus::Success, // 1xx, 3xx treated as success
+                    _ => HttpStatus::AssertionFailed, // Outside --success-status
                 };
 
                 // Add to recent times (ring buffer)
-                if self.recent_times.len() >= MAX_SAMPLES {
-                    self.recent_times.pop_front();
-                }
-                self.recent_times.push_back(response_time);
+                self.recent_times.push(response_time);
+                self.response_histogram.observe(response_time);
 
                 // Calculate statistics
                 self.calculate_stats();
             }
             None => {
                 // Network error
+                self.network_errors += 1;
                 self.status = HttpStatus::NetworkError;
                 self.last_status_code = None;
             }
@@ -103,6 +258,115 @@ impl HttpStats {
             self.success_rate_percent =
                 (self.requests_successful as f64 / self.requests_sent as f64) * 100.0;
         }
+
+        let detail = ResultDetail {
+            status: Some(ProbeStatus::Http(self.status)),
+            success,
+            status_code,
+            content_size: self.last_content_size,
+        };
+        let latency = status_code.map(|_| response_time);
+        self.timeline.push(self.last_updated, latency, detail, self.last_error.as_deref());
+        self.status_log.record(self.last_updated, &previous, &self.status);
+    }
+
+    /// Replay a check from an earlier session; see `Stats::restore`
+    pub fn restore(&mut self, at: DateTime<Local>, sample: Option<Duration>, detail: ResultDetail) {
+        self.requests_sent += 1;
+        if detail.success {
+            self.requests_successful += 1;
+        }
+        if self.recent_results.len() >= MAX_SAMPLES {
+            self.recent_results.pop_front();
+        }
+        self.recent_results.push_back(sample);
+
+        match sample {
+            Some(response_time) => {
+                if let Some(code) = detail.status_code {
+                    self.count_status(code, 1);
+                }
+                self.recent_times.push(response_time);
+                self.response_histogram.observe(response_time);
+                self.calculate_stats();
+            }
+            None => self.network_errors += 1,
+        }
+        self.timeline.restore(at, sample, detail);
+        self.success_rate_percent =
+            (self.requests_successful as f64 / self.requests_sent as f64) * 100.0;
+    }
+
+    /// See `Stats::restore_counts`
+    pub fn restore_counts(&mut self, status_code: Option<u16>, results: u64, answered: u64, successful: u64) {
+        self.requests_sent += results;
+        self.requests_successful += successful;
+        self.network_errors += results - answered;
+        if let Some(code) = status_code {
+            self.count_status(code, answered);
+        }
+        if self.requests_sent > 0 {
+            self.success_rate_percent =
+                (self.requests_successful as f64 / self.requests_sent as f64) * 100.0;
+        }
+    }
+
+    pub fn restore_bucket(&mut self, resolution: Resolution, bucket: Bucket) {
+        self.timeline.restore_bucket(resolution, bucket);
+    }
+
+    pub fn restore_change(&mut self, change: StatusChange) {
+        self.status_log.restore(change);
+    }
+
+    /// The last `MAX_SAMPLES` response times, oldest first, with `None` for network errors
+    pub fn history(&self) -> impl Iterator<Item = Option<Duration>> + '_ {
+        self.recent_results.iter().copied()
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn status_log(&self) -> &StatusLog {
+        &self.status_log
+    }
+
+    fn count_status(&mut self, code: u16, responses: u64) {
+        *self.status_counts.entry(code).or_insert(0) += responses;
+        if let Some(count) = class_index(code / 100).and_then(|i| self.class_counts.get_mut(i)) {
+            *count += responses;
+        }
+    }
+
+    fn calculate_timings(&mut self) {
+        let count = self.recent_timings.len() as u32;
+        if count == 0 {
+            return;
+        }
+
+        let average = |phase: fn(&HttpTimings) -> Duration| -> Duration {
+            self.recent_timings.iter().map(phase).sum::<Duration>() / count
+        };
+
+        // TLS is averaged over the checks that had a handshake
+        let tls: Vec<Duration> = self.recent_timings.iter().filter_map(|t| t.tls).collect();
+
+        self.avg_timings = Some(HttpTimings {
+            connect: average(|t| t.connect),
+            tls: (!tls.is_empty()).then(|| tls.iter().sum::<Duration>() / tls.len() as u32),
+            ttfb: average(|t| t.ttfb),
+            transfer: average(|t| t.transfer),
+            total: average(|t| t.total),
+        });
+    }
+
+    /// Responses in a status class, e.g. `class_count(5)` for 5xx
+    pub fn class_count(&self, class: u16) -> u64 {
+        class_index(class)
+            .and_then(|i| self.class_counts.get(i))
+            .copied()
+            .unwrap_or(0)
     }
 
     fn calculate_stats(&mut self) {
@@ -110,12 +374,49 @@ impl HttpStats {
             return;
         }
 
-        // Calculate average
-        let sum: Duration = self.recent_times.iter().sum();
-        self.avg_response_time = Some(sum / self.recent_times.len() as u32);
+        self.avg_response_time = self.recent_times.mean();
+        self.min_response_time = self.recent_times.min();
+        self.max_response_time = self.recent_times.max();
+        self.spread = self.recent_times.spread();
+    }
+}
+
+/// Slot in `class_counts` for a status class (1 for 1xx ... 5 for 5xx)
+fn class_index(class: u16) -> Option<usize> {
+    class.checked_sub(1).map(usize::from).filter(|i| *i < 5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(code: u16, success: bool) -> HttpResult {
+        HttpResult {
+            status_code: Some(code),
+            success,
+            error: None,
+            ..HttpResult::failed(Duration::from_millis(5), String::new())
+        }
+    }
 
-        // Calculate min/max
-        self.min_response_time = self.recent_times.iter().min().copied();
-        self.max_response_time = self.recent_times.iter().max().copied();
+    #[test]
+    fn status_follows_the_success_decision() {
+        let mut stats = HttpStats::new();
+        // e.g. --success-status 404
+        stats.update(response(404, true));
+        assert_eq!(stats.status, HttpStatus::Success);
+        // e.g. --success-status 200 and a redirect
+        stats.update(response(301, false));
+        assert_eq!(stats.status, HttpStatus::AssertionFailed);
+        stats.update(response(503, false));
+        assert_eq!(stats.status, HttpStatus::ServerError);
+        stats.update(response(403, false));
+        assert_eq!(stats.status, HttpStatus::ClientError);
+        stats.update(HttpResult {
+            error: Some("Body: connection reset".to_string()),
+            ..response(200, false)
+        });
+        assert_eq!(stats.status, HttpStatus::NetworkError);
+        assert_eq!(stats.requests_successful, 1);
     }
 }
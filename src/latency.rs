@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Percentiles and spread of the samples in a `LatencyWindow`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencySpread {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    /// Population standard deviation
+    pub std_dev: Duration,
+    /// RFC 3550 interarrival jitter: running mean deviation of consecutive deltas
+    pub jitter: Duration,
+}
+
+/// Ring buffer of the most recent latency samples.
+///
+/// A sorted copy and exact integer sums are kept up to date on every push, so
+/// mean, standard deviation and percentiles never rescan the window. Jitter is
+/// smoothed over the whole session rather than the window, as RFC 3550 does.
+#[derive(Clone, Debug)]
+pub struct LatencyWindow {
+    capacity: usize,
+    /// Samples in arrival order
+    samples: VecDeque<Duration>,
+    /// The same samples, sorted ascending
+    sorted: Vec<Duration>,
+    /// Sum of samples in nanoseconds
+    sum: u128,
+    /// Sum of squared samples in nanoseconds²
+    sum_sq: u128,
+    /// Smoothed jitter in nanoseconds
+    jitter: f64,
+    last: Option<Duration>,
+}
+
+impl LatencyWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            sorted: Vec::with_capacity(capacity),
+            sum: 0,
+            sum_sq: 0,
+            jitter: 0.0,
+            last: None,
+        }
+    }
+
+    /// Add a sample, evicting the oldest once the window is full
+    pub fn push(&mut self, sample: Duration) {
+        if self.samples.len() >= self.capacity {
+            if let Some(evicted) = self.samples.pop_front() {
+                let nanos = evicted.as_nanos();
+                self.sum -= nanos;
+                self.sum_sq -= nanos * nanos;
+                if let Ok(i) = self.sorted.binary_search(&evicted) {
+                    self.sorted.remove(i);
+                }
+            }
+        }
+
+        let nanos = sample.as_nanos();
+        self.sum += nanos;
+        self.sum_sq += nanos * nanos;
+        let i = self.sorted.partition_point(|s| *s < sample);
+        self.sorted.insert(i, sample);
+        self.samples.push_back(sample);
+
+        // J += (|D| - J) / 16
+        if let Some(last) = self.last {
+            let delta = sample.abs_diff(last).as_nanos() as f64;
+            self.jitter += (delta - self.jitter) / 16.0;
+        }
+        self.last = Some(sample);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(nanos_to_duration(self.sum / self.samples.len() as u128))
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.sorted.first().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.sorted.last().copied()
+    }
+
+    /// Nearest-rank percentile, `p` in 0..=100
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.sorted.is_empty() {
+            return None;
+        }
+        let rank = (p / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted.get(rank.clamp(1, self.sorted.len()) - 1).copied()
+    }
+
+    pub fn std_dev(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean = self.sum as f64 / n;
+        let variance = (self.sum_sq as f64 / n - mean * mean).max(0.0);
+        Some(Duration::from_nanos(variance.sqrt() as u64))
+    }
+
+    pub fn jitter(&self) -> Duration {
+        Duration::from_nanos(self.jitter as u64)
+    }
+
+    /// Percentiles, standard deviation and jitter, once there is at least one sample
+    pub fn spread(&self) -> Option<LatencySpread> {
+        Some(LatencySpread {
+            p50: self.percentile(50.0)?,
+            p90: self.percentile(90.0)?,
+            p95: self.percentile(95.0)?,
+            p99: self.percentile(99.0)?,
+            std_dev: self.std_dev()?,
+            jitter: self.jitter(),
+        })
+    }
+}
+
+/// Upper bounds of the response-time histogram buckets, in seconds
+pub const HISTOGRAM_BOUNDS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Session-long histogram of latencies over `HISTOGRAM_BOUNDS`, for metrics export
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    /// Samples per bucket (not cumulative); the last slot counts samples above every bound
+    counts: [u64; HISTOGRAM_BOUNDS.len() + 1],
+    sum: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn observe(&mut self, sample: Duration) {
+        let secs = sample.as_secs_f64();
+        let i = HISTOGRAM_BOUNDS.partition_point(|bound| *bound < secs);
+        self.counts[i] += 1;
+        self.sum += sample;
+    }
+
+    /// `(upper bound, samples at or below it)` for every bound, then `+Inf`
+    pub fn cumulative(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        HISTOGRAM_BOUNDS
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .zip(self.counts.iter().scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            }))
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+}
+
+fn nanos_to_duration(nanos: u128) -> Duration {
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn filled(capacity: usize, samples: &[u64]) -> LatencyWindow {
+        let mut window = LatencyWindow::new(capacity);
+        for sample in samples {
+            window.push(ms(*sample));
+        }
+        window
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let empty = LatencyWindow::new(10);
+        assert_eq!(empty.percentile(50.0), None);
+        assert_eq!(empty.spread(), None);
+
+        // Pushed out of order, 1..=10 ms
+        let window = filled(10, &[7, 3, 10, 1, 9, 2, 8, 4, 6, 5]);
+        assert_eq!(window.percentile(0.0), Some(ms(1)));
+        assert_eq!(window.percentile(50.0), Some(ms(5)));
+        assert_eq!(window.percentile(90.0), Some(ms(9)));
+        assert_eq!(window.percentile(95.0), Some(ms(10)));
+        assert_eq!(window.percentile(100.0), Some(ms(10)));
+        assert_eq!((window.min(), window.max()), (Some(ms(1)), Some(ms(10))));
+
+        let single = filled(10, &[42]);
+        assert_eq!(single.percentile(1.0), Some(ms(42)));
+        assert_eq!(single.percentile(99.0), Some(ms(42)));
+    }
+
+    #[test]
+    fn population_standard_deviation() {
+        // Mean 5 ms, squared deviations sum to 32 over 8 samples: variance 4, deviation 2 ms
+        let window = filled(8, &[2, 4, 4, 4, 5, 5, 7, 9]);
+        assert_eq!(window.mean(), Some(ms(5)));
+        assert_eq!(window.std_dev(), Some(ms(2)));
+
+        assert_eq!(filled(8, &[3, 3, 3]).std_dev(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn jitter_follows_the_rfc_3550_recurrence() {
+        let samples = [10, 30, 20, 20, 50, 10];
+        let mut window = LatencyWindow::new(2);
+        let mut expected = 0.0f64;
+        let mut last: Option<u64> = None;
+        for sample in samples {
+            window.push(ms(sample));
+            if let Some(last) = last {
+                let delta = sample.abs_diff(last) as f64 * 1e6;
+                expected += (delta - expected) / 16.0;
+            }
+            last = Some(sample);
+            assert_eq!(window.jitter(), Duration::from_nanos(expected as u64));
+        }
+        // The first delta moves jitter by 1/16 of 20 ms
+        assert_eq!(filled(2, &[10, 30]).jitter(), Duration::from_nanos(1_250_000));
+        // Smoothed over the session, not just the two samples still in the window
+        assert_ne!(window.jitter(), filled(2, &[50, 10]).jitter());
+    }
+
+    #[test]
+    fn eviction_keeps_sums_and_order_consistent() {
+        let mut window = LatencyWindow::new(5);
+        // Duplicates make sure eviction removes exactly one copy
+        for (i, sample) in [5, 1, 5, 9, 3, 5, 2, 8, 5, 5, 7, 1, 4].into_iter().enumerate() {
+            window.push(ms(sample));
+
+            assert_eq!(window.samples.len(), (i + 1).min(5));
+            let mut sorted: Vec<Duration> = window.samples.iter().copied().collect();
+            sorted.sort();
+            assert_eq!(window.sorted, sorted);
+            assert_eq!(window.sum, window.samples.iter().map(|s| s.as_nanos()).sum::<u128>());
+            assert_eq!(window.sum_sq, window.samples.iter().map(|s| s.as_nanos() * s.as_nanos()).sum::<u128>());
+        }
+        // Only the last five remain: 5, 5, 7, 1, 4
+        assert_eq!(window.mean(), Some(Duration::from_micros(4400)));
+        assert_eq!((window.min(), window.max()), (Some(ms(1)), Some(ms(7))));
+        assert_eq!(window.percentile(50.0), Some(ms(5)));
+    }
+}
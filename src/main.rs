@@ -1,13 +1,31 @@
+mod alerts;
+mod api;
 mod app;
+mod config;
+mod db;
+mod export;
+mod headless;
+mod history;
 mod http_checker;
 mod http_stats;
+mod latency;
+mod messages;
+mod metrics;
 mod parser;
 mod pinger;
+mod resolver;
 mod stats;
+mod tcp_checker;
+mod tcp_stats;
 mod ui;
+mod webhooks;
 
 use anyhow::{Context, Result};
 use app::App;
+use config::{
+    AlertRule, ApiListen, ExportFormat, ExportSettings, HostFilter, HttpScheme, ProbeOverrides,
+    ProbeSpec, Settings, Webhook, WebhookFormat,
+};
 use crossterm::{
     event::{self, Event},
     execute,
@@ -15,19 +33,39 @@ use crossterm::{
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 use stats::AppMode;
+use std::collections::HashMap;
 use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Setup panic hook to restore terminal
-    setup_panic_hook();
-
     // Parse command-line arguments
     let args: Vec<String> = std::env::args().collect();
-    let mut mode = AppMode::Icmp;
-    let mut port = 80;
+    let mut modes: Vec<AppMode> = Vec::new();
+    let mut port = None;
+    let mut extra_probes: Vec<ProbeSpec> = Vec::new();
+    let mut host_probes = HashMap::new();
+    let mut http_scheme = HttpScheme::Auto;
     let mut xml_path = "output.xml".to_string();
+    let mut resolve_dns = true;
+    let mut ipv6_scope_id = None;
+    let mut probe_overrides = ProbeOverrides::default();
+    let mut host_overrides = HashMap::new();
+    let mut alert_rules = Vec::new();
+    let mut on_alert = None;
+    let mut webhooks = Vec::new();
+    let mut webhook_rate = 20;
+    let mut webhook_template = None;
+    let mut headless = false;
+    let mut host_filter: Option<Vec<HostFilter>> = None;
+    let mut output = None;
+    let mut metrics_listen: Option<SocketAddr> = None;
+    let mut api_listen = None;
+    let mut db_path: Option<PathBuf> = None;
+    let mut db_retention = Duration::from_secs(30 * 86400);
+    let mut export = ExportSettings::default();
 
     let mut i = 1;
     while i < args.len() {
@@ -36,20 +74,272 @@ async fn main() -> Result<()> {
                 print_help();
                 return Ok(());
             }
-            "--http" => mode = AppMode::Http,
+            "--icmp" => modes.push(AppMode::Icmp),
+            "--http" => modes.push(AppMode::Http),
+            "--tcp" => modes.push(AppMode::Tcp),
+            "--no-dns" => resolve_dns = false,
+            "--interface" => {
+                i += 1;
+                if i < args.len() {
+                    ipv6_scope_id = pinger::interface_scope_id(&args[i]);
+                    if ipv6_scope_id.is_none() {
+                        eprintln!("Unknown network interface: {}", args[i]);
+                        return Ok(());
+                    }
+                } else {
+                    eprintln!("--interface requires a value");
+                    return Ok(());
+                }
+            }
             "--port" => {
                 i += 1;
                 if i < args.len() {
-                    port = args[i].parse().unwrap_or_else(|_| {
+                    port = Some(args[i].parse().unwrap_or_else(|_| {
                         eprintln!("Invalid port number: {}", args[i]);
                         eprintln!("Using default port 80");
                         80
-                    });
+                    }));
                 } else {
                     eprintln!("--port requires a value");
                     return Ok(());
                 }
             }
+            "--scheme" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("--scheme requires a value");
+                    return Ok(());
+                };
+                match HttpScheme::parse(value) {
+                    Ok(scheme) => http_scheme = scheme,
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--insecure" => probe_overrides.accept_invalid_certs = Some(true),
+            "--read-body" => probe_overrides.read_body = Some(true),
+            flag @ ("--interval" | "--timeout" | "--size" | "--pattern" | "--ttl" | "--sni"
+            | "--method" | "--path" | "--header" | "--body" | "--success-status" | "--expect-status"
+            | "--expect-body" | "--expect-regex" | "--max-time" | "--max-body") => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("{} requires a value", flag);
+                    return Ok(());
+                };
+                if let Err(e) = probe_overrides.set(&flag[2..], value) {
+                    eprintln!("{:#}", e);
+                    return Ok(());
+                }
+            }
+            "--probe" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--probe requires a value");
+                    return Ok(());
+                };
+                match ProbeSpec::parse(spec) {
+                    Ok(probe) => extra_probes.push(probe),
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--probes" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--probes requires a value");
+                    return Ok(());
+                };
+                match ProbeSpec::parse_host_spec(spec) {
+                    Ok((ip, probes)) => {
+                        host_probes.insert(ip, probes);
+                    }
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--override" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--override requires a value");
+                    return Ok(());
+                };
+                match ProbeOverrides::parse_host_spec(spec) {
+                    Ok((ip, overrides)) => {
+                        host_overrides.insert(ip, overrides);
+                    }
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--alert" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--alert requires a value");
+                    return Ok(());
+                };
+                match AlertRule::parse(spec) {
+                    Ok(rule) => alert_rules.push(rule),
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--on-alert" => {
+                i += 1;
+                let Some(command) = args.get(i) else {
+                    eprintln!("--on-alert requires a value");
+                    return Ok(());
+                };
+                on_alert = Some(command.clone());
+            }
+            "--webhook" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--webhook requires a value");
+                    return Ok(());
+                };
+                match Webhook::parse(spec) {
+                    Ok(webhook) => webhooks.push(webhook),
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--webhook-template" => {
+                i += 1;
+                let Some(template) = args.get(i) else {
+                    eprintln!("--webhook-template requires a value");
+                    return Ok(());
+                };
+                if let Err(e) = webhooks::check_template(template) {
+                    eprintln!("{:#}", e);
+                    return Ok(());
+                }
+                webhook_template = Some(template.clone());
+            }
+            "--webhook-rate" => {
+                i += 1;
+                match args.get(i).map(|v| v.parse::<u32>()) {
+                    Some(Ok(rate)) if rate > 0 => webhook_rate = rate,
+                    _ => {
+                        eprintln!("--webhook-rate requires a positive number of deliveries per minute");
+                        return Ok(());
+                    }
+                }
+            }
+            "--headless" => headless = true,
+            "--hosts" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--hosts requires a value");
+                    return Ok(());
+                };
+                match HostFilter::parse_list(spec) {
+                    Ok(filters) => host_filter = Some(filters),
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--output" => {
+                i += 1;
+                let Some(path) = args.get(i) else {
+                    eprintln!("--output requires a value");
+                    return Ok(());
+                };
+                output = Some(PathBuf::from(path));
+            }
+            "--metrics-listen" => {
+                i += 1;
+                match args.get(i).map(|v| v.parse::<SocketAddr>()) {
+                    Some(Ok(addr)) => metrics_listen = Some(addr),
+                    _ => {
+                        eprintln!("--metrics-listen requires an address such as 127.0.0.1:9184");
+                        return Ok(());
+                    }
+                }
+            }
+            "--api-listen" => {
+                i += 1;
+                let Some(spec) = args.get(i) else {
+                    eprintln!("--api-listen requires a value");
+                    return Ok(());
+                };
+                match ApiListen::parse(spec) {
+                    Ok(listen) => api_listen = Some(listen),
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--db" => {
+                i += 1;
+                let Some(path) = args.get(i) else {
+                    eprintln!("--db requires a value");
+                    return Ok(());
+                };
+                db_path = Some(PathBuf::from(path));
+            }
+            "--db-retention" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("--db-retention requires a value");
+                    return Ok(());
+                };
+                match config::parse_duration(value) {
+                    Ok(retention) => db_retention = retention,
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
+            "--export-dir" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("--export-dir requires a value");
+                    return Ok(());
+                };
+                export.dir = PathBuf::from(value);
+            }
+            "--export-name" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("--export-name requires a value");
+                    return Ok(());
+                };
+                if value.is_empty() || value.contains(std::path::is_separator) || value.contains("..") || value == "." {
+                    eprintln!("--export-name must be a file name without a directory or '..'");
+                    return Ok(());
+                }
+                export.name = value.to_string();
+            }
+            "--export-format" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    eprintln!("--export-format requires a value");
+                    return Ok(());
+                };
+                match ExportFormat::parse_list(value) {
+                    Ok(formats) => export.formats = formats,
+                    Err(e) => {
+                        eprintln!("{:#}", e);
+                        return Ok(());
+                    }
+                }
+            }
             path if !path.starts_with("--") => xml_path = path.to_string(),
             _ => {
                 eprintln!("Unknown option: {}", args[i]);
@@ -61,16 +351,85 @@ async fn main() -> Result<()> {
     }
 
     // Parse XML file
-    let ips = parser::parse_nmap_xml(&xml_path)
+    let hosts = parser::parse_nmap_xml(&xml_path)
         .context(format!("Failed to parse nmap XML: {}", xml_path))?;
 
-    if ips.is_empty() {
+    if hosts.is_empty() {
         eprintln!("No hosts found in {}. Please run nmap first.", xml_path);
         return Ok(());
     }
 
-    // Create app with selected mode and port
-    let mut app = App::new(ips, mode, port);
+    // Mode flags combine (e.g. --icmp --http); ICMP alone is the default
+    let mut probes: Vec<ProbeSpec> = modes
+        .iter()
+        .map(|mode| match mode {
+            AppMode::Icmp => ProbeSpec::Icmp,
+            AppMode::Http => ProbeSpec::Http(port.unwrap_or(80)),
+            AppMode::Tcp => ProbeSpec::Tcp(port),
+        })
+        .collect();
+    probes.extend(extra_probes);
+    if probes.is_empty() {
+        probes.push(ProbeSpec::Icmp);
+    }
+
+    if let Some(template) = webhook_template {
+        for webhook in webhooks.iter_mut().filter(|w| w.format == WebhookFormat::Generic) {
+            webhook.format = WebhookFormat::Template(template.clone());
+        }
+    }
+
+    // Notifications without rules would never fire; watch for unreachable hosts by default
+    if alert_rules.is_empty() && (on_alert.is_some() || !webhooks.is_empty()) {
+        alert_rules.push(AlertRule::Unreachable(5));
+    }
+
+    // Create app with selected probes and probe settings
+    let settings = Settings {
+        probes,
+        http_scheme,
+        host_probes,
+        ipv6_scope_id,
+        resolve_dns,
+        probe_overrides,
+        host_overrides,
+        alert_rules,
+        on_alert,
+        webhooks,
+        webhook_rate,
+        export,
+    };
+    let mut app = App::new(hosts, settings);
+
+    if let Some(path) = &db_path {
+        let database = db::Database::open(path, db_retention, &app.stats, app.messages.sender())?;
+        app.attach_database(database);
+    }
+
+    if let Some(addr) = metrics_listen {
+        metrics::spawn(addr, app.stats.clone(), app.hostnames.clone()).await?;
+    }
+
+    if let Some(listen) = &api_listen {
+        app.attach_api(api::spawn(listen).await?);
+    }
+
+    if let Some(filters) = &host_filter {
+        app.select_where(|ip| filters.iter().any(|f| f.contains(ip)));
+    }
+
+    if headless {
+        // Without --hosts, monitor everything in the scan
+        if host_filter.is_none() {
+            app.select_where(|_| true);
+        }
+        // A bell would corrupt JSON Lines written to stdout
+        app.alerts.disable_bell();
+        return headless::run(&mut app, output.as_deref()).await;
+    }
+
+    // Setup panic hook to restore terminal
+    setup_panic_hook();
 
     // Setup terminal
     enable_raw_mode()?;
@@ -98,6 +457,8 @@ async fn run_app<B: ratatui::backend::Backend>(
     app: &mut App,
 ) -> Result<()> {
     loop {
+        app.tick();
+
         // Render UI
         terminal.draw(|f| ui::render(app, f))?;
 
@@ -129,14 +490,83 @@ fn setup_panic_hook() {
 }
 
 fn print_help() {
-    println!("TUI Ether Pinger 🔌 - Network monitoring with ICMP and HTTP modes");
+    println!("TUI Ether Pinger 🔌 - Network monitoring with ICMP, HTTP and TCP modes");
     println!();
     println!("USAGE:");
     println!("    tui-ether-pinger [OPTIONS] [XML_FILE]");
     println!();
     println!("OPTIONS:");
-    println!("    --http              Use HTTP checking mode (default: ICMP ping)");
-    println!("    --port PORT         Port to check (default: 80, HTTP mode only)");
+    println!("    --icmp              Use ICMP ping mode (default when no mode is given)");
+    println!("    --http              Use HTTP checking mode");
+    println!("    --tcp               Use TCP connect mode (handshake time to a port)");
+    println!("                        Mode flags can be combined to run several probes per host");
+    println!("    --port PORT         Port to check (default: 80 for HTTP; TCP uses nmap's open ports)");
+    println!("    --probe SPEC        Add a probe for every host: icmp, http[:PORT], https[:PORT] or tcp[:PORT]");
+    println!("    --scheme SCHEME     auto, http or https for HTTP probes (default: auto = HTTPS on 443/8443");
+    println!("                        or where nmap saw ssl/http)");
+    println!("    --sni NAME          Hostname for TLS SNI and the Host header (requests still go to the IP)");
+    println!("    --insecure          Accept invalid TLS certificates (self-signed, expired, wrong name)");
+    println!("    --method METHOD     HTTP request method: GET, HEAD or POST (default: GET)");
+    println!("    --path PATH         HTTP request path (default: /)");
+    println!("    --header 'K: V'     Extra HTTP request header, e.g. Host or Authorization (repeatable)");
+    println!("    --body TEXT         HTTP request body");
+    println!("    --success-status SET");
+    println!("                        Status codes counted as successful requests (default: 200-399)");
+    println!("    --expect-status SET Status codes that pass, e.g. 200,204 or 200-399 (default: any)");
+    println!("    --expect-body TEXT  Fail HTTP checks whose body doesn't contain TEXT");
+    println!("    --expect-regex RE   Fail HTTP checks whose body doesn't match RE");
+    println!("    --max-time MS       Fail HTTP checks slower than MS milliseconds");
+    println!("    --read-body         Hash HTTP bodies to detect content changes");
+    println!("    --max-body BYTES    Stop reading HTTP bodies after BYTES (default: 1048576)");
+    println!("    --probes IP@SPEC,...");
+    println!("                        Per-host probe list, replacing the global probes for that host");
+    println!("    --interval MS       Probe interval in milliseconds (default: 1000)");
+    println!("    --timeout MS        Probe timeout in milliseconds (default: 2000 ICMP, 5000 HTTP)");
+    println!("    --size BYTES        ICMP payload size (default: 56)");
+    println!("    --pattern HEX       ICMP payload fill pattern, e.g. ff or deadbeef (default: 00)");
+    println!("    --ttl N             ICMP TTL / hop limit (default: OS default)");
+    println!("    --override IP@K=V,...");
+    println!("                        Per-host probe settings (keys: interval, timeout, size, pattern, ttl,");
+    println!("                        sni, insecure, method, path, header, body, success-status,");
+    println!("                        expect-status, expect-body, expect-regex, max-time, read-body,");
+    println!("                        max-body)");
+    println!("    --interface IFACE   Interface (name or index) for link-local IPv6 targets");
+    println!("    --no-dns            Skip reverse DNS lookups for hosts without an nmap hostname");
+    println!("    --alert RULE        Alert when a running probe matches RULE (repeatable):");
+    println!("                          unreachable=N           N failed probes in a row");
+    println!("                          loss=PCT[/WINDOW]       loss above PCT% (window default 60s)");
+    println!("                          latency=MS[/WINDOW]     average latency above MS");
+    println!("                          http-5xx                last HTTP response was a 5xx");
+    println!("    --on-alert CMD      Shell command run when an alert fires or recovers, with");
+    println!("                        PINGER_ALERT_{{STATE,HOST,HOSTNAME,PROBE,RULE,MESSAGE,TIME,DURATION}}");
+    println!("    --webhook [slack:]URL");
+    println!("                        POST alerts and recoveries as JSON to URL (repeatable); the");
+    println!("                        slack: prefix sends a Slack-compatible {{\"text\": ...}} body");
+    println!("    --webhook-template JSON");
+    println!("                        Body for webhooks without the slack: prefix, with {{state}},");
+    println!("                        {{host}}, {{hostname}}, {{probe}}, {{rule}}, {{message}}, {{time}},");
+    println!("                        {{duration}} and {{suppressed}} replaced by JSON-escaped values");
+    println!("    --webhook-rate N    Most deliveries per webhook per minute (default: 20)");
+    println!("                        With --on-alert or --webhook but no --alert, unreachable=5 is used");
+    println!("    --hosts LIST        Start monitoring these hosts: comma-separated IPs or CIDR");
+    println!("                        networks, e.g. 10.0.0.5,10.0.1.0/24");
+    println!("    --headless          Run without the TUI, writing every probe result, status change,");
+    println!("                        alert and task message as JSON Lines; monitors all hosts unless");
+    println!("                        --hosts is given. Stops cleanly on SIGINT/SIGTERM");
+    println!("    --output FILE       Append headless output to FILE instead of stdout");
+    println!("    --metrics-listen ADDR");
+    println!("                        Serve OpenMetrics for Prometheus at http://ADDR/metrics,");
+    println!("                        e.g. 127.0.0.1:9184 (works with the TUI and --headless)");
+    println!("    --api-listen ADDR   Serve the JSON control API on a loopback HOST:PORT or unix:PATH");
+    println!("                        (list hosts and stats, start/stop, add hosts, pause, export)");
+    println!("    --db FILE           Record every result and status change in a SQLite database and");
+    println!("                        reload recent history from it on startup");
+    println!("    --db-retention AGE  Delete database rows older than AGE, e.g. 7d or 12h (default: 30d)");
+    println!("    --export-dir DIR    Write exports to DIR, creating it if needed (default: .)");
+    println!("    --export-name NAME  Export file name without extension; {{ts}} becomes the export");
+    println!("                        time (default: stats_export_{{ts}})");
+    println!("    --export-format LIST");
+    println!("                        Comma-separated export formats: csv, json, md, html (default: csv)");
     println!("    -h, --help          Print this help message");
     println!();
     println!("ARGS:");
@@ -145,10 +575,18 @@ fn print_help() {
     println!("CONTROLS:");
     println!("    ↑/↓ or k/j          Navigate host list");
     println!("    Space               Toggle selection (start/stop monitoring)");
+    println!("    Enter               Host detail: latency chart, stats, scan data, events");
+    println!("    w                   Cycle the detail chart window (1m, 10m, 1h, 24h)");
     println!("    a                   Select all hosts");
     println!("    d                   Deselect all hosts");
     println!("    p                   Pause/resume all monitoring");
-    println!("    s                   Export statistics to CSV file");
+    println!("    s                   Export statistics in the --export-format formats");
+    println!("    c                   Show HTTP status-code breakdown for the highlighted host");
+    println!("    t                   Toggle HTTP timing breakdown (connect, TLS, TTFB, transfer)");
+    println!("    x                   Toggle percentile, std dev and jitter columns");
+    println!("    e                   Toggle the status-change event log pane");
+    println!("    PgUp/PgDn, Home     Scroll the event log, back to the newest");
+    println!("    l                   Message log: task errors and export results");
     println!("    q or Esc            Quit");
     println!();
     println!("EXAMPLES:");
@@ -161,6 +599,31 @@ fn print_help() {
     println!("    # HTTP mode on custom port");
     println!("    sudo tui-ether-pinger --http --port 8080");
     println!();
+    println!("    # Ping every host and check its web server at the same time");
+    println!("    sudo tui-ether-pinger --icmp --http");
+    println!();
+    println!("    # TCP handshake timing on every open port nmap found");
+    println!("    tui-ether-pinger --tcp");
+    println!();
+    println!("    # Ping every 200ms, but give one slow host a 5s timeout");
+    println!("    sudo tui-ether-pinger --interval 200 --override 10.0.0.5@timeout=5000");
+    println!();
+    println!("    # Health-check an endpoint, failing unless it answers 200 with \"ok\" within 500ms");
+    println!("    tui-ether-pinger --http --path /health --expect-status 200 --expect-body ok --max-time 500");
+    println!();
+    println!("    # Alert on 3 lost pings in a row or >20% loss over 5 minutes, and log it");
+    println!("    sudo tui-ether-pinger --alert unreachable=3 --alert loss=20/5m \\");
+    println!("        --on-alert 'echo \"$PINGER_ALERT_STATE $PINGER_ALERT_HOST $PINGER_ALERT_MESSAGE\" >> alerts.log'");
+    println!();
+    println!("    # Post outages to a Slack channel");
+    println!("    sudo tui-ether-pinger --alert unreachable=5 --webhook slack:https://hooks.slack.com/services/...");
+    println!();
+    println!("    # Log results for one subnet as JSON Lines, e.g. under systemd");
+    println!("    sudo tui-ether-pinger --headless --hosts 10.0.0.0/24 --output pinger.jsonl");
+    println!();
+    println!("    # Write an HTML report and JSON snapshot to reports/ with 's'");
+    println!("    sudo tui-ether-pinger --export-dir reports --export-format html,json");
+    println!();
     println!("    # With custom nmap XML file");
     println!("    sudo tui-ether-pinger --http --port 443 scan_results.xml");
     println!();
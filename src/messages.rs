@@ -0,0 +1,157 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// How long a toast stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// Log entries kept; older ones are dropped
+const LOG_CAPACITY: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub at: DateTime<Local>,
+    pub level: Level,
+    pub text: String,
+}
+
+/// Handle for probe tasks and background threads to report problems
+/// without writing to the terminal the TUI is drawing on
+#[derive(Clone)]
+pub struct MessageSender(mpsc::UnboundedSender<Message>);
+
+impl MessageSender {
+    pub fn error(&self, text: impl Into<String>) {
+        self.send(Level::Error, text.into());
+    }
+
+    pub fn warning(&self, text: impl Into<String>) {
+        self.send(Level::Warning, text.into());
+    }
+
+    fn send(&self, level: Level, text: String) {
+        let _ = self.0.send(Message {
+            at: Local::now(),
+            level,
+            text,
+        });
+    }
+}
+
+/// Transient toasts plus a scrollable log of everything reported, shown in the `l` pane
+pub struct Messages {
+    log: VecDeque<Message>,
+    /// Entries ever logged, so readers can tell which ones are new
+    total: usize,
+    toast: Option<(Message, Instant)>,
+    /// Errors logged since the log pane was last opened
+    unread_errors: usize,
+    /// How many of the newest entries the log pane scrolls past
+    pub scroll: usize,
+    sender: mpsc::UnboundedSender<Message>,
+    receiver: mpsc::UnboundedReceiver<Message>,
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            log: VecDeque::new(),
+            total: 0,
+            toast: None,
+            unread_errors: 0,
+            scroll: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl Messages {
+    pub fn sender(&self) -> MessageSender {
+        MessageSender(self.sender.clone())
+    }
+
+    /// Show `text` briefly and keep it in the log
+    pub fn toast(&mut self, level: Level, text: impl Into<String>) {
+        let message = Message {
+            at: Local::now(),
+            level,
+            text: text.into(),
+        };
+        self.toast = Some((message.clone(), Instant::now()));
+        self.push(message);
+    }
+
+    /// Add `text` to the log only
+    pub fn log(&mut self, level: Level, text: impl Into<String>) {
+        self.push(Message {
+            at: Local::now(),
+            level,
+            text: text.into(),
+        });
+    }
+
+    /// Move what tasks reported since the last call into the log
+    pub fn receive(&mut self) {
+        while let Ok(message) = self.receiver.try_recv() {
+            self.push(message);
+        }
+    }
+
+    /// The toast to draw, until it expires
+    pub fn current_toast(&self) -> Option<&Message> {
+        self.toast
+            .as_ref()
+            .filter(|(_, shown)| shown.elapsed() < TOAST_DURATION)
+            .map(|(message, _)| message)
+    }
+
+    /// Kept entries, oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Message> + ExactSizeIterator {
+        self.log.iter()
+    }
+
+    /// Entries logged after the first `seen`, with the new total to pass next time
+    pub fn since(&self, seen: usize) -> (impl Iterator<Item = &Message>, usize) {
+        let new = (self.total - seen.min(self.total)).min(self.log.len());
+        (self.log.iter().skip(self.log.len() - new), self.total)
+    }
+
+    pub fn unread_errors(&self) -> usize {
+        self.unread_errors
+    }
+
+    pub fn mark_read(&mut self) {
+        self.unread_errors = 0;
+    }
+
+    fn push(&mut self, message: Message) {
+        if message.level == Level::Error {
+            self.unread_errors += 1;
+        }
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(message);
+        self.total += 1;
+    }
+}
@@ -0,0 +1,332 @@
+use crate::resolver::HostnameCache;
+use crate::stats::{Probe, Stats, StatsMap};
+use anyhow::{Context, Result};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+const CONTENT_TYPE_OPENMETRICS: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Pause after a failed accept before trying again
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Every status a probe kind can report, for the `pinger_status` state set
+const PING_STATES: [&str; 4] = ["NotStarted", "Active", "Timeout", "Unreachable"];
+const HTTP_STATES: [&str; 6] = [
+    "NotStarted",
+    "Success",
+    "ClientError",
+    "ServerError",
+    "NetworkError",
+    "AssertionFailed",
+];
+const TCP_STATES: [&str; 5] = ["NotStarted", "Open", "Refused", "Filtered", "Error"];
+
+/// Bind `addr` and serve the current statistics at `/metrics` in the background.
+///
+/// Binding happens before returning so a taken or invalid address fails at startup.
+pub async fn spawn(addr: SocketAddr, stats: StatsMap, hostnames: HostnameCache) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context(format!("Failed to listen for metrics on {}", addr))?;
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                // Errors such as EMFILE persist for a while, so don't spin on them
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            };
+            let stats = stats.clone();
+            let hostnames = hostnames.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let response = handle(&request, &stats, &hostnames);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(
+    request: &Request<Incoming>,
+    stats: &StatsMap,
+    hostnames: &HostnameCache,
+) -> Response<Full<Bytes>> {
+    let (status, content_type, body) = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => (
+            StatusCode::OK,
+            CONTENT_TYPE_OPENMETRICS,
+            render(stats, hostnames),
+        ),
+        (_, "/metrics") => (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        ),
+        _ => (StatusCode::NOT_FOUND, "text/plain", "Not found; try /metrics\n".to_string()),
+    };
+
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, value);
+    }
+    response
+}
+
+/// One labelled sample line per metric family, grouped under its HELP/TYPE header
+#[derive(Default)]
+struct Family {
+    lines: Vec<String>,
+}
+
+impl Family {
+    fn push(&mut self, name: &str, labels: &str, value: f64) {
+        self.lines.push(format!("{}{{{}}} {}", name, labels, format_value(value)));
+    }
+}
+
+/// Render every probe in the stats map in the OpenMetrics text format
+fn render(stats: &StatsMap, hostnames: &HostnameCache) -> String {
+    let mut up = Family::default();
+    let mut status = Family::default();
+    let mut last = Family::default();
+    let mut avg = Family::default();
+    let mut loss = Family::default();
+    let mut probes = Family::default();
+    let mut http_code = Family::default();
+    let mut histogram = Family::default();
+
+    let stats_lock = stats.read();
+    let hostnames = hostnames.read();
+    let mut keys: Vec<_> = stats_lock.keys().copied().collect();
+    keys.sort();
+
+    for key in keys {
+        let Some(stats) = stats_lock.get(&key) else {
+            continue;
+        };
+        let (ip, probe) = key;
+        let hostname = hostnames.get(&ip).cloned().flatten();
+        let labels = labels(ip, hostname.as_deref(), probe);
+
+        let (current, states, last_latency, avg_latency, loss_ratio, sent) = match stats {
+            Stats::Ping(s) => (
+                format!("{:?}", s.status),
+                &PING_STATES[..],
+                s.last_latency,
+                s.avg_latency,
+                s.packet_loss_percent / 100.0,
+                s.packets_sent,
+            ),
+            Stats::Http(s) => (
+                format!("{:?}", s.status),
+                &HTTP_STATES[..],
+                s.last_response_time,
+                s.avg_response_time,
+                failure_ratio(s.requests_sent, s.success_rate_percent),
+                s.requests_sent,
+            ),
+            Stats::Tcp(s) => (
+                format!("{:?}", s.status),
+                &TCP_STATES[..],
+                s.last_connect_time,
+                s.avg_connect_time,
+                failure_ratio(s.attempts, s.success_rate_percent),
+                s.attempts,
+            ),
+        };
+
+        // Hosts never selected have nothing to report yet
+        if sent == 0 {
+            continue;
+        }
+
+        let answered = stats.timeline().trailing_lost() == 0;
+        up.push("pinger_up", &labels, if answered { 1.0 } else { 0.0 });
+        for state in states {
+            let value = if *state == current { 1.0 } else { 0.0 };
+            status.push(
+                "pinger_status",
+                &format!("{},pinger_status=\"{}\"", labels, state),
+                value,
+            );
+        }
+        if let Some(latency) = last_latency {
+            last.push("pinger_latency_last_seconds", &labels, latency.as_secs_f64());
+        }
+        if let Some(latency) = avg_latency {
+            avg.push("pinger_latency_avg_seconds", &labels, latency.as_secs_f64());
+        }
+        loss.push("pinger_loss_ratio", &labels, loss_ratio);
+        probes.push("pinger_probes_total", &labels, sent as f64);
+
+        if let Stats::Http(s) = stats {
+            if let Some(code) = s.last_status_code {
+                http_code.push("pinger_http_status_code", &labels, code as f64);
+            }
+            let h = &s.response_histogram;
+            for (bound, count) in h.cumulative() {
+                histogram.push(
+                    "pinger_http_response_time_seconds_bucket",
+                    &format!("{},le=\"{}\"", labels, format_value(bound)),
+                    count as f64,
+                );
+            }
+            histogram.push("pinger_http_response_time_seconds_count", &labels, h.count() as f64);
+            histogram.push(
+                "pinger_http_response_time_seconds_sum",
+                &labels,
+                h.sum().as_secs_f64(),
+            );
+        }
+    }
+
+    let mut out = String::new();
+    for (name, kind, unit, help, family) in [
+        ("pinger_up", "gauge", None, "Whether the last probe got an answer", &up),
+        ("pinger_status", "stateset", None, "Current probe status", &status),
+        ("pinger_latency_last_seconds", "gauge", Some("seconds"), "Latency of the last answered probe", &last),
+        (
+            "pinger_latency_avg_seconds",
+            "gauge",
+            Some("seconds"),
+            "Average latency of recent answered probes",
+            &avg,
+        ),
+        ("pinger_loss_ratio", "gauge", Some("ratio"), "Share of probes that failed, 0 to 1", &loss),
+        ("pinger_probes", "counter", None, "Probes sent this session", &probes),
+        ("pinger_http_status_code", "gauge", None, "Status code of the last HTTP response", &http_code),
+        (
+            "pinger_http_response_time_seconds",
+            "histogram",
+            Some("seconds"),
+            "HTTP response times this session",
+            &histogram,
+        ),
+    ] {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        if let Some(unit) = unit {
+            let _ = writeln!(out, "# UNIT {} {}", name, unit);
+        }
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        for line in &family.lines {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn labels(ip: IpAddr, hostname: Option<&str>, probe: Probe) -> String {
+    format!(
+        "ip=\"{}\",hostname=\"{}\",probe=\"{}\"",
+        ip,
+        escape(hostname.unwrap_or("")),
+        probe
+    )
+}
+
+/// Failed share of attempts from a success percentage; 0 before the first attempt
+fn failure_ratio(attempts: u64, success_percent: f64) -> f64 {
+    if attempts == 0 {
+        0.0
+    } else {
+        (100.0 - success_percent) / 100.0
+    }
+}
+
+/// Escape a label value: backslash, double quote and newline
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        "+Inf".to_string()
+    } else {
+        format!("{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_stats::HttpResult;
+    use std::time::Duration;
+
+    #[test]
+    fn renders_openmetrics_exposition() {
+        let stats = StatsMap::default();
+        let hostnames = HostnameCache::default();
+        let ip: IpAddr = [192, 0, 2, 7].into();
+        hostnames.write().insert(ip, Some("we\\ird \"host\"\nname".to_string()));
+        {
+            let mut stats_lock = stats.write();
+            let mut http = Probe::Http(80).new_stats();
+            if let Stats::Http(s) = &mut http {
+                s.update(HttpResult {
+                    status_code: Some(200),
+                    success: true,
+                    error: None,
+                    ..HttpResult::failed(Duration::from_millis(30), String::new())
+                });
+            }
+            stats_lock.insert((ip, Probe::Http(80)), http);
+            // Never sent, so left out
+            stats_lock.insert(([192, 0, 2, 8].into(), Probe::Icmp), Probe::Icmp.new_stats());
+        }
+
+        let out = render(&stats, &hostnames);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.last(), Some(&"# EOF"));
+        assert_eq!(out.matches("# EOF").count(), 1);
+
+        let labels = r#"ip="192.0.2.7",hostname="we\\ird \"host\"\nname",probe="HTTP:80""#;
+        for expected in [
+            "# TYPE pinger_up gauge".to_string(),
+            "# TYPE pinger_status stateset".to_string(),
+            "# TYPE pinger_latency_last_seconds gauge".to_string(),
+            "# UNIT pinger_latency_last_seconds seconds".to_string(),
+            "# UNIT pinger_loss_ratio ratio".to_string(),
+            "# TYPE pinger_probes counter".to_string(),
+            "# TYPE pinger_http_response_time_seconds histogram".to_string(),
+            "# UNIT pinger_http_response_time_seconds seconds".to_string(),
+            format!("pinger_up{{{}}} 1", labels),
+            format!("pinger_status{{{},pinger_status=\"Success\"}} 1", labels),
+            format!("pinger_status{{{},pinger_status=\"ServerError\"}} 0", labels),
+            format!("pinger_latency_last_seconds{{{}}} 0.03", labels),
+            format!("pinger_probes_total{{{}}} 1", labels),
+            format!("pinger_http_status_code{{{}}} 200", labels),
+            format!("pinger_http_response_time_seconds_bucket{{{},le=\"0.025\"}} 0", labels),
+            format!("pinger_http_response_time_seconds_bucket{{{},le=\"0.05\"}} 1", labels),
+            format!("pinger_http_response_time_seconds_bucket{{{},le=\"+Inf\"}} 1", labels),
+            format!("pinger_http_response_time_seconds_count{{{}}} 1", labels),
+        ] {
+            assert!(lines.contains(&expected.as_str()), "missing {:?} in\n{}", expected, out);
+        }
+        // Units only on families whose names carry them
+        assert!(!out.contains("# UNIT pinger_up "));
+        assert!(!out.contains("192.0.2.8"));
+        // Every family has TYPE and HELP before its samples
+        assert_eq!(out.matches("# TYPE ").count(), out.matches("# HELP ").count());
+    }
+}
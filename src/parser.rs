@@ -1,42 +1,161 @@
 use anyhow::{Context, Result};
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
 use std::net::IpAddr;
 
-/// Parse nmap XML output file and extract IP addresses from hosthints
-pub fn parse_nmap_xml(path: &str) -> Result<Vec<IpAddr>> {
+/// A host discovered by nmap, with whatever metadata the scan recorded
+#[derive(Clone, Debug)]
+pub struct ScannedHost {
+    /// Primary address used for probing (first IPv4, otherwise first IPv6)
+    pub ip: IpAddr,
+    /// Host state from `<status state="...">` (e.g. "up", "down")
+    pub state: Option<String>,
+    /// Every IP address reported for the host
+    pub addresses: Vec<IpAddr>,
+    pub mac: Option<String>,
+    pub mac_vendor: Option<String>,
+    pub hostnames: Vec<String>,
+    pub ports: Vec<ScannedPort>,
+}
+
+/// A port entry from the `<ports>` section of a host
+#[derive(Clone, Debug)]
+pub struct ScannedPort {
+    pub protocol: String,
+    pub port: u16,
+    pub state: String,
+    pub service: Option<String>,
+    /// Tunnel reported by service detection (e.g. "ssl")
+    pub tunnel: Option<String>,
+}
+
+impl ScannedHost {
+    pub fn new(ip: IpAddr) -> Self {
+        Self {
+            ip,
+            state: None,
+            addresses: vec![ip],
+            mac: None,
+            mac_vendor: None,
+            hostnames: Vec::new(),
+            ports: Vec::new(),
+        }
+    }
+
+    /// Ports nmap reported as open
+    pub fn open_ports(&self) -> impl Iterator<Item = &ScannedPort> {
+        self.ports.iter().filter(|p| p.state == "open")
+    }
+
+    /// Whether a port should be spoken to over TLS: 443/8443, or nmap saw ssl/http or https
+    pub fn is_tls_port(&self, port: u16) -> bool {
+        let scanned_tls = self.ports.iter().any(|p| {
+            p.port == port
+                && p.protocol == "tcp"
+                && (p.tunnel.as_deref() == Some("ssl") || p.service.as_deref() == Some("https"))
+        });
+        scanned_tls || port == 443 || port == 8443
+    }
+}
+
+/// Host record being assembled while walking a `<host>` element
+#[derive(Default)]
+struct HostBuilder {
+    state: Option<String>,
+    addresses: Vec<IpAddr>,
+    mac: Option<String>,
+    mac_vendor: Option<String>,
+    hostnames: Vec<String>,
+    ports: Vec<ScannedPort>,
+}
+
+impl HostBuilder {
+    fn add_address(&mut self, e: &BytesStart) {
+        let addr = attr_value(e, b"addr");
+        match attr_value(e, b"addrtype").as_deref() {
+            Some("mac") => {
+                self.mac = addr;
+                self.mac_vendor = attr_value(e, b"vendor");
+            }
+            _ => {
+                if let Some(ip) = addr.and_then(|a| a.parse::<IpAddr>().ok()) {
+                    self.addresses.push(ip);
+                }
+            }
+        }
+    }
+
+    fn build(self) -> Option<ScannedHost> {
+        let ip = self
+            .addresses
+            .iter()
+            .find(|ip| ip.is_ipv4())
+            .or_else(|| self.addresses.first())
+            .copied()?;
+
+        Some(ScannedHost {
+            ip,
+            state: self.state,
+            addresses: self.addresses,
+            mac: self.mac,
+            mac_vendor: self.mac_vendor,
+            hostnames: self.hostnames,
+            ports: self.ports,
+        })
+    }
+}
+
+/// Parse nmap XML output file and extract every scanned host.
+///
+/// Full `<host>` records are preferred; addresses that only appear in
+/// `<hosthint>` elements (emitted by verbose scans) are kept as bare hosts.
+pub fn parse_nmap_xml(path: &str) -> Result<Vec<ScannedHost>> {
     let content = std::fs::read_to_string(path)
         .context(format!("Failed to read XML file: {}", path))?;
+    parse_hosts(&content)
+}
 
-    let mut reader = Reader::from_str(&content);
+fn parse_hosts(content: &str) -> Result<Vec<ScannedHost>> {
+    let mut reader = Reader::from_str(content);
     reader.config_mut().trim_text(true);
 
-    let mut ips = Vec::new();
+    let mut hosts: Vec<ScannedHost> = Vec::new();
+    let mut hint_ips: Vec<IpAddr> = Vec::new();
     let mut buf = Vec::new();
     let mut in_hosthint = false;
+    let mut current: Option<HostBuilder> = None;
+    let mut current_port: Option<ScannedPort> = None;
 
     loop {
         match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) if e.name().as_ref() == b"hosthint" => {
-                in_hosthint = true;
-            }
-            Ok(Event::End(e)) if e.name().as_ref() == b"hosthint" => {
-                in_hosthint = false;
-            }
-            Ok(Event::Empty(e)) if e.name().as_ref() == b"address" && in_hosthint => {
-                // Extract addr attribute
-                for attr in e.attributes() {
-                    if let Ok(attr) = attr {
-                        if attr.key.as_ref() == b"addr" {
-                            if let Ok(addr_str) = std::str::from_utf8(&attr.value) {
-                                if let Ok(ip) = addr_str.parse::<IpAddr>() {
-                                    ips.push(ip);
-                                }
-                            }
-                        }
+            Ok(Event::Start(e)) => match e.name().as_ref() {
+                b"hosthint" => in_hosthint = true,
+                b"host" => current = Some(HostBuilder::default()),
+                b"port" => current_port = parse_port(&e),
+                _ => handle_element(&e, in_hosthint, &mut hint_ips, &mut current, &mut current_port),
+            },
+            Ok(Event::Empty(e)) => match e.name().as_ref() {
+                b"port" => {
+                    if let (Some(host), Some(port)) = (current.as_mut(), parse_port(&e)) {
+                        host.ports.push(port);
                     }
                 }
-            }
+                _ => handle_element(&e, in_hosthint, &mut hint_ips, &mut current, &mut current_port),
+            },
+            Ok(Event::End(e)) => match e.name().as_ref() {
+                b"hosthint" => in_hosthint = false,
+                b"host" => {
+                    if let Some(host) = current.take().and_then(HostBuilder::build) {
+                        hosts.push(host);
+                    }
+                }
+                b"port" => {
+                    if let (Some(host), Some(port)) = (current.as_mut(), current_port.take()) {
+                        host.ports.push(port);
+                    }
+                }
+                _ => {}
+            },
             Ok(Event::Eof) => break,
             Err(e) => {
                 return Err(anyhow::anyhow!("XML parse error at position {}: {}",
@@ -47,9 +166,179 @@ pub fn parse_nmap_xml(path: &str) -> Result<Vec<IpAddr>> {
         buf.clear();
     }
 
-    if ips.is_empty() {
+    // Keep hint-only addresses that never got a full host record
+    for ip in hint_ips {
+        if !hosts.iter().any(|h| h.addresses.contains(&ip)) {
+            hosts.push(ScannedHost::new(ip));
+        }
+    }
+
+    if hosts.is_empty() {
         return Err(anyhow::anyhow!("No IP addresses found in XML file"));
     }
 
-    Ok(ips)
+    Ok(hosts)
+}
+
+/// Handle the leaf elements shared by `Start` and `Empty` events
+fn handle_element(
+    e: &BytesStart,
+    in_hosthint: bool,
+    hint_ips: &mut Vec<IpAddr>,
+    current: &mut Option<HostBuilder>,
+    current_port: &mut Option<ScannedPort>,
+) {
+    match e.name().as_ref() {
+        b"address" if in_hosthint => {
+            if let Some(ip) = attr_value(e, b"addr").and_then(|a| a.parse::<IpAddr>().ok()) {
+                if !hint_ips.contains(&ip) {
+                    hint_ips.push(ip);
+                }
+            }
+        }
+        b"address" => {
+            if let Some(host) = current.as_mut() {
+                host.add_address(e);
+            }
+        }
+        b"status" => {
+            if let Some(host) = current.as_mut() {
+                host.state = attr_value(e, b"state");
+            }
+        }
+        b"hostname" => {
+            if let (Some(host), Some(name)) = (current.as_mut(), attr_value(e, b"name")) {
+                if !host.hostnames.contains(&name) {
+                    host.hostnames.push(name);
+                }
+            }
+        }
+        b"state" => {
+            if let (Some(port), Some(state)) = (current_port.as_mut(), attr_value(e, b"state")) {
+                port.state = state;
+            }
+        }
+        b"service" => {
+            if let Some(port) = current_port.as_mut() {
+                port.service = attr_value(e, b"name");
+                port.tunnel = attr_value(e, b"tunnel");
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_port(e: &BytesStart) -> Option<ScannedPort> {
+    let port = attr_value(e, b"portid")?.parse().ok()?;
+    Some(ScannedPort {
+        protocol: attr_value(e, b"protocol").unwrap_or_else(|| "tcp".to_string()),
+        port,
+        state: "unknown".to_string(),
+        service: None,
+        tunnel: None,
+    })
+}
+
+fn attr_value(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == key)
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed `nmap -sV -v -oX` output: hints for two hosts, one of which never gets a full record
+    const SCAN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE nmaprun>
+<nmaprun scanner="nmap" args="nmap -sV -v -oX scan.xml 192.168.1.0/24" version="7.94">
+<hosthint><status state="up" reason="arp-response"/>
+<address addr="192.168.1.10" addrtype="ipv4"/>
+<address addr="AA:BB:CC:DD:EE:FF" addrtype="mac" vendor="Acme"/>
+</hosthint>
+<hosthint><status state="up" reason="arp-response"/>
+<address addr="192.168.1.99" addrtype="ipv4"/>
+</hosthint>
+<host starttime="1700000000" endtime="1700000010"><status state="up" reason="arp-response" reason_ttl="0"/>
+<address addr="fe80::a8bb:ccff:fedd:eeff" addrtype="ipv6"/>
+<address addr="192.168.1.10" addrtype="ipv4"/>
+<address addr="AA:BB:CC:DD:EE:FF" addrtype="mac" vendor="Acme &amp; Sons"/>
+<hostnames>
+<hostname name="router.lan" type="PTR"/>
+<hostname name="gateway.lan" type="user"/>
+<hostname name="router.lan" type="PTR"/>
+</hostnames>
+<ports><extraports state="closed" count="996"/>
+<port protocol="tcp" portid="22"><state state="open" reason="syn-ack" reason_ttl="64"/><service name="ssh" product="OpenSSH" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="8443"><state state="open" reason="syn-ack" reason_ttl="64"/><service name="http" tunnel="ssl" method="probed" conf="10"/></port>
+<port protocol="tcp" portid="25"><state state="filtered" reason="no-response" reason_ttl="0"/></port>
+<port protocol="udp" portid="53"><state state="open" reason="udp-response" reason_ttl="64"/><service name="domain" method="table" conf="3"/></port>
+</ports>
+</host>
+<host><status state="down" reason="no-response" reason_ttl="0"/>
+<address addr="2001:db8::5" addrtype="ipv6"/>
+</host>
+<runstats><finished time="1700000020" exit="success"/><hosts up="2" down="1" total="3"/></runstats>
+</nmaprun>
+"#;
+
+    #[test]
+    fn parses_full_host_records() {
+        let hosts = parse_hosts(SCAN).unwrap();
+        let ips: Vec<String> = hosts.iter().map(|h| h.ip.to_string()).collect();
+        // Full records in file order, then the hint that never got one
+        assert_eq!(ips, ["192.168.1.10", "2001:db8::5", "192.168.1.99"]);
+
+        let router = &hosts[0];
+        assert_eq!(router.state.as_deref(), Some("up"));
+        let addresses: Vec<String> = router.addresses.iter().map(|a| a.to_string()).collect();
+        assert_eq!(addresses, ["fe80::a8bb:ccff:fedd:eeff", "192.168.1.10"]);
+        assert_eq!(router.mac.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(router.mac_vendor.as_deref(), Some("Acme & Sons"));
+        assert_eq!(router.hostnames, ["router.lan", "gateway.lan"]);
+
+        let ports: Vec<String> = router
+            .ports
+            .iter()
+            .map(|p| {
+                let service = [p.service.as_deref(), p.tunnel.as_deref()].into_iter().flatten();
+                let line = format!("{}/{} {} {}", p.protocol, p.port, p.state, service.collect::<Vec<_>>().join("+"));
+                line.trim_end().to_string()
+            })
+            .collect();
+        assert_eq!(ports, ["tcp/22 open ssh", "tcp/8443 open http+ssl", "tcp/25 filtered", "udp/53 open domain"]);
+        let open: Vec<u16> = router.open_ports().map(|p| p.port).collect();
+        assert_eq!(open, [22, 8443, 53]);
+        assert!(router.is_tls_port(8443));
+        assert!(!router.is_tls_port(22));
+        assert!(router.is_tls_port(443));
+    }
+
+    #[test]
+    fn keeps_down_and_hint_only_hosts() {
+        let hosts = parse_hosts(SCAN).unwrap();
+
+        // Down hosts stay in the list with their state; IPv6-only hosts probe their IPv6 address
+        let down = &hosts[1];
+        assert_eq!(down.state.as_deref(), Some("down"));
+        assert_eq!(down.ip, "2001:db8::5".parse::<IpAddr>().unwrap());
+        assert!(down.ports.is_empty() && down.hostnames.is_empty() && down.mac.is_none());
+
+        // A hinted host with a full record isn't listed twice; one without becomes a bare host
+        assert_eq!(hosts.iter().filter(|h| h.addresses.contains(&"192.168.1.10".parse().unwrap())).count(), 1);
+        let hinted = &hosts[2];
+        assert_eq!(hinted.addresses, [hinted.ip]);
+        assert_eq!(hinted.state, None);
+        assert!(hinted.mac.is_none() && hinted.ports.is_empty());
+    }
+
+    #[test]
+    fn rejects_scans_without_addresses() {
+        let empty = r#"<?xml version="1.0"?><nmaprun><host><status state="down"/></host></nmaprun>"#;
+        let error = parse_hosts(empty).unwrap_err();
+        assert_eq!(error.to_string(), "No IP addresses found in XML file");
+    }
 }
@@ -1,42 +1,94 @@
-use crate::stats::Stats;
-use parking_lot::RwLock;
+use crate::config::ProbeConfig;
+use crate::stats::{Probe, Stats, StatsMap};
 use rand::Rng;
 use std::collections::HashMap;
+use std::io;
 use std::net::IpAddr;
 use std::sync::Arc;
-use std::time::Duration;
-use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, ICMP};
 
-/// Start an async ping task for a specific IP address
+/// ICMP clients shared by every ping task: one socket per address family
+/// (and per TTL, since TTL is a socket option).
+///
+/// surge-ping demultiplexes replies on a shared socket by (host, identifier,
+/// sequence), so each task only needs its own `PingIdentifier`. Clients are
+/// handed out behind an `Arc` because dropping any `Client` clone shuts down
+/// the socket for every task sharing it.
+pub struct IcmpClients {
+    clients: HashMap<(bool, Option<u32>), Arc<Client>>,
+    next_ident: u16,
+}
+
+impl Default for IcmpClients {
+    fn default() -> Self {
+        Self {
+            clients: HashMap::new(),
+            // Random starting point so concurrent instances don't collide on raw sockets
+            next_ident: rand::thread_rng().gen::<u16>(),
+        }
+    }
+}
+
+impl IcmpClients {
+    /// Get the shared client for the target's address family and TTL, creating it on first use
+    pub fn client_for(&mut self, ip: IpAddr, ttl: Option<u32>) -> io::Result<Arc<Client>> {
+        let key = (ip.is_ipv6(), ttl);
+        if let Some(client) = self.clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let kind = match ip {
+            IpAddr::V4(_) => ICMP::V4,
+            IpAddr::V6(_) => ICMP::V6,
+        };
+        let mut builder = Config::builder().kind(kind);
+        if let Some(ttl) = ttl {
+            builder = builder.ttl(ttl);
+        }
+
+        let client = Arc::new(Client::new(&builder.build())?);
+        self.clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Allocate a distinct identifier for the next ping task
+    pub fn next_identifier(&mut self) -> PingIdentifier {
+        let ident = PingIdentifier(self.next_ident);
+        self.next_ident = self.next_ident.wrapping_add(1);
+        ident
+    }
+}
+
+/// Start an async ping task for a specific IP address on a shared ICMP client.
+///
+/// Link-local IPv6 targets are sent out of the interface given by `scope_id`.
 pub async fn start_ping_task(
     ip: IpAddr,
-    stats: Arc<RwLock<HashMap<IpAddr, Stats>>>,
+    client: Arc<Client>,
+    ident: PingIdentifier,
+    scope_id: Option<u32>,
+    config: ProbeConfig,
+    stats: StatsMap,
     mut shutdown: tokio::sync::watch::Receiver<bool>,
 ) {
-    // Create ping client
-    let config = Config::default();
-    let client = match Client::new(&config) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Failed to create ping client for {}: {}", ip, e);
-            return;
+    let mut pinger = client.pinger(ip, ident).await;
+    pinger.timeout(config.timeout);
+    if let (IpAddr::V6(v6), Some(scope)) = (ip, scope_id) {
+        if v6.is_unicast_link_local() {
+            pinger.scope_id(scope);
         }
-    };
-
-    // Create pinger with random identifier
-    let ping_id = rand::thread_rng().gen::<u16>();
-    let mut pinger = client.pinger(ip, PingIdentifier(ping_id)).await;
+    }
 
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut interval = tokio::time::interval(config.interval);
     let mut seq = 0u16;
-    let payload = [0u8; 56];
+    let payload = config.payload();
 
     loop {
         tokio::select! {
             _ = interval.tick() => {
                 // Send ping and wait for response with timeout
                 let ping_future = pinger.ping(PingSequence(seq), &payload);
-                let result = tokio::time::timeout(Duration::from_secs(2), ping_future).await;
+                let result = tokio::time::timeout(config.timeout, ping_future).await;
 
                 let latency = match result {
                     Ok(Ok((_, duration))) => Some(duration),
@@ -46,7 +98,7 @@ pub async fn start_ping_task(
                 // Update statistics
                 {
                     let mut stats_lock = stats.write();
-                    if let Some(Stats::Ping(host_stats)) = stats_lock.get_mut(&ip) {
+                    if let Some(Stats::Ping(host_stats)) = stats_lock.get_mut(&(ip, Probe::Icmp)) {
                         host_stats.update(latency);
                     }
                 }
@@ -60,3 +112,112 @@ pub async fn start_ping_task(
         }
     }
 }
+
+/// Resolve an interface name (e.g. "eth0") or numeric index to an IPv6 scope id
+pub fn interface_scope_id(interface: &str) -> Option<u32> {
+    if let Ok(index) = interface.parse::<u32>() {
+        return Some(index);
+    }
+
+    let name = std::ffi::CString::new(interface).ok()?;
+    // SAFETY: `name` is a valid NUL-terminated string for the duration of the call
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::AppMode;
+    use std::collections::HashSet;
+    use std::time::Duration;
+    use tokio::sync::watch;
+
+    /// The shared client for `ip`; these tests need CAP_NET_RAW (or root), so they are ignored by default
+    fn shared_client(clients: &mut IcmpClients, ip: IpAddr) -> Arc<Client> {
+        clients
+            .client_for(ip, None)
+            .unwrap_or_else(|e| panic!("cannot open an ICMP socket for {} (run `just stress-loopback`): {}", ip, e))
+    }
+
+    /// Run a ping task per target until each has `replies` answers or `limit` passes
+    async fn ping_all(targets: Vec<(IpAddr, Arc<Client>, PingIdentifier)>, replies: u64, limit: Duration) -> StatsMap {
+        let stats = StatsMap::default();
+        let (shutdown, watcher) = watch::channel(false);
+        let config = ProbeConfig {
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_secs(1),
+            ..ProbeConfig::defaults(AppMode::Icmp)
+        };
+        for (ip, client, ident) in targets {
+            stats.write().insert((ip, Probe::Icmp), Probe::Icmp.new_stats());
+            tokio::spawn(start_ping_task(
+                ip,
+                client,
+                ident,
+                None,
+                config.clone(),
+                stats.clone(),
+                watcher.clone(),
+            ));
+        }
+
+        let answered = |stats: &StatsMap| {
+            stats.read().values().all(|s| matches!(s, Stats::Ping(p) if p.packets_received >= replies))
+        };
+        let _ = tokio::time::timeout(limit, async {
+            while !answered(&stats) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await;
+        let _ = shutdown.send(true);
+        stats
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a raw ICMP socket"]
+    async fn pings_ipv6_loopback_through_the_shared_client() {
+        let mut clients = IcmpClients::default();
+        let ip: IpAddr = "::1".parse().unwrap();
+        let client = shared_client(&mut clients, ip);
+        // Every IPv6 target shares one socket, and dropping a handle to it leaves it open
+        drop(shared_client(&mut clients, "fe80::1".parse().unwrap()));
+        assert_eq!(clients.clients.len(), 1);
+
+        let ident = clients.next_identifier();
+        let stats = ping_all(vec![(ip, client, ident)], 3, Duration::from_secs(5)).await;
+        let stats_lock = stats.read();
+        let Some(Stats::Ping(ping)) = stats_lock.get(&(ip, Probe::Icmp)) else {
+            panic!("missing ping stats");
+        };
+        assert!(ping.packets_received >= 3, "{} of {} answered", ping.packets_received, ping.packets_sent);
+        assert!(ping.avg_latency.is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a raw ICMP socket"]
+    async fn many_loopback_pingers_share_one_socket() {
+        const PINGERS: usize = 1000;
+        let mut clients = IcmpClients::default();
+        let mut targets = Vec::new();
+        for i in 0..PINGERS {
+            let ip = IpAddr::from([127, 0, (i / 250) as u8, (i % 250 + 1) as u8]);
+            let client = shared_client(&mut clients, ip);
+            targets.push((ip, client, clients.next_identifier()));
+        }
+        assert_eq!(clients.clients.len(), 1);
+        let idents: HashSet<u16> = targets.iter().map(|(_, _, ident)| ident.0).collect();
+        assert_eq!(idents.len(), PINGERS);
+
+        // Replies are matched by (host, identifier, sequence), so each pinger only counts its own
+        let stats = ping_all(targets, 3, Duration::from_secs(20)).await;
+        let stats_lock = stats.read();
+        let short: Vec<_> = stats_lock
+            .iter()
+            .filter(|(_, s)| !matches!(s, Stats::Ping(p) if p.packets_received >= 3))
+            .map(|((ip, _), _)| ip)
+            .collect();
+        assert!(short.is_empty(), "{} of {} pingers got fewer than 3 replies: {:?}", short.len(), PINGERS, short);
+    }
+}
@@ -0,0 +1,88 @@
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Maximum time to wait for a single reverse lookup
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of reverse lookups allowed in flight at once
+const MAX_CONCURRENT_LOOKUPS: usize = 16;
+
+/// Resolved hostnames keyed by IP. `None` caches a failed lookup so it isn't retried.
+pub type HostnameCache = Arc<RwLock<HashMap<IpAddr, Option<String>>>>;
+
+/// Reverse-resolve an IP through the system resolver (which honours /etc/hosts).
+/// Results, including failures, are stored in the cache.
+pub async fn reverse_lookup(ip: IpAddr, cache: HostnameCache) -> Option<String> {
+    if let Some(cached) = cache.read().get(&ip) {
+        return cached.clone();
+    }
+
+    // getnameinfo() blocks, so run it off the async workers
+    let lookup = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip));
+    let name = match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+        Ok(Ok(Ok(name))) if name != ip.to_string() => Some(name),
+        _ => None,
+    };
+
+    cache.write().insert(ip, name.clone());
+    name
+}
+
+/// Resolve every IP in the background, filling the cache as answers arrive
+pub fn spawn_reverse_lookups(ips: Vec<IpAddr>, cache: HostnameCache) {
+    let limit = Arc::new(Semaphore::new(MAX_CONCURRENT_LOOKUPS));
+
+    for ip in ips {
+        let cache = Arc::clone(&cache);
+        let limit = Arc::clone(&limit);
+        tokio::spawn(async move {
+            let Ok(_permit) = limit.acquire().await else {
+                return;
+            };
+            reverse_lookup(ip, cache).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cached_answers_are_not_looked_up_again() {
+        let cache = HostnameCache::default();
+        let named: IpAddr = [192, 0, 2, 1].into();
+        let failed: IpAddr = [192, 0, 2, 2].into();
+        cache.write().insert(named, Some("router.lan".to_string()));
+        cache.write().insert(failed, None);
+
+        assert_eq!(reverse_lookup(named, cache.clone()).await.as_deref(), Some("router.lan"));
+        assert_eq!(reverse_lookup(failed, cache.clone()).await, None);
+        assert_eq!(cache.read().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn lookups_fill_the_cache_including_failures() {
+        let cache = HostnameCache::default();
+        let loopback: IpAddr = [127, 0, 0, 1].into();
+        let name = reverse_lookup(loopback, cache.clone()).await;
+        // A lookup that only echoes the address back counts as no name
+        assert_ne!(name.as_deref(), Some("127.0.0.1"));
+        assert_eq!(cache.read().get(&loopback), Some(&name));
+
+        let ips: Vec<IpAddr> = (1..=20).map(|i| IpAddr::from([127, 0, 1, i])).collect();
+        spawn_reverse_lookups(ips.clone(), cache.clone());
+        for _ in 0..300 {
+            if ips.iter().all(|ip| cache.read().contains_key(ip)) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(ips.iter().all(|ip| cache.read().contains_key(ip)));
+        assert!(cache.read().values().flatten().all(|name| name.parse::<IpAddr>().is_err()));
+    }
+}
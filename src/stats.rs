@@ -1,23 +1,151 @@
-use crate::http_stats::HttpStats;
+use crate::history::{Bucket, Resolution, ResultDetail, StatusChange, StatusLog, Timeline};
+use crate::http_stats::{HttpStats, HttpStatus};
+use crate::latency::{LatencySpread, LatencyWindow};
+use crate::tcp_stats::{TcpStats, TcpStatus};
 use chrono::{DateTime, Local};
-use std::collections::VecDeque;
+use parking_lot::RwLock;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 const MAX_SAMPLES: usize = 100;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum AppMode {
     Icmp,
     Http,
+    Tcp,
+}
+
+/// A single probe run against a host; a host may run several at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Probe {
+    Icmp,
+    Http(u16),
+    Https(u16),
+    Tcp(u16),
+}
+
+impl Probe {
+    pub fn mode(&self) -> AppMode {
+        match self {
+            Probe::Icmp => AppMode::Icmp,
+            Probe::Http(_) | Probe::Https(_) => AppMode::Http,
+            Probe::Tcp(_) => AppMode::Tcp,
+        }
+    }
+
+    /// Empty statistics of the right kind for this probe
+    pub fn new_stats(&self) -> Stats {
+        match self {
+            Probe::Icmp => Stats::Ping(PingStats::new()),
+            Probe::Http(_) | Probe::Https(_) => Stats::Http(Box::new(HttpStats::new())),
+            Probe::Tcp(_) => Stats::Tcp(TcpStats::new()),
+        }
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Probe::Icmp => write!(f, "ICMP"),
+            Probe::Http(port) => write!(f, "HTTP:{}", port),
+            Probe::Https(port) => write!(f, "HTTPS:{}", port),
+            Probe::Tcp(port) => write!(f, "TCP:{}", port),
+        }
+    }
+}
+
+/// Statistics are tracked per (host, probe)
+pub type ProbeKey = (IpAddr, Probe);
+
+/// Shared statistics map written by probe tasks and read by the UI
+pub type StatsMap = Arc<RwLock<HashMap<ProbeKey, Stats>>>;
+
+/// Status of any probe kind, small enough to keep with every result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbeStatus {
+    Ping(PingStatus),
+    Http(HttpStatus),
+    Tcp(TcpStatus),
+}
+
+impl fmt::Display for ProbeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeStatus::Ping(status) => write!(f, "{:?}", status),
+            ProbeStatus::Http(status) => write!(f, "{:?}", status),
+            ProbeStatus::Tcp(status) => write!(f, "{:?}", status),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum Stats {
     Ping(PingStats),
-    Http(HttpStats),
+    Http(Box<HttpStats>),
+    Tcp(TcpStats),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl Stats {
+    /// Timestamped results of the last hour, whatever the probe kind
+    pub fn timeline(&self) -> &Timeline {
+        match self {
+            Stats::Ping(s) => s.timeline(),
+            Stats::Http(s) => s.timeline(),
+            Stats::Tcp(s) => s.timeline(),
+        }
+    }
+
+    pub fn status_log(&self) -> &StatusLog {
+        match self {
+            Stats::Ping(s) => s.status_log(),
+            Stats::Http(s) => s.status_log(),
+            Stats::Tcp(s) => s.status_log(),
+        }
+    }
+
+    /// Replay a result from an earlier session into history, counters and averages,
+    /// leaving the current status alone
+    pub fn restore(&mut self, at: DateTime<Local>, sample: Option<Duration>, detail: ResultDetail) {
+        match self {
+            Stats::Ping(s) => s.restore(at, sample, detail),
+            Stats::Http(s) => s.restore(at, sample, detail),
+            Stats::Tcp(s) => s.restore(at, sample, detail),
+        }
+    }
+
+    /// Count results from an earlier session that are only restored as rollups:
+    /// `results` of them, `answered` with a latency and `successful` by the
+    /// probe's own measure. `status_code` groups them for HTTP.
+    pub fn restore_counts(&mut self, status_code: Option<u16>, results: u64, answered: u64, successful: u64) {
+        match self {
+            Stats::Ping(s) => s.restore_counts(results, answered),
+            Stats::Http(s) => s.restore_counts(status_code, results, answered, successful),
+            Stats::Tcp(s) => s.restore_counts(results, answered),
+        }
+    }
+
+    pub fn restore_bucket(&mut self, resolution: Resolution, bucket: Bucket) {
+        match self {
+            Stats::Ping(s) => s.restore_bucket(resolution, bucket),
+            Stats::Http(s) => s.restore_bucket(resolution, bucket),
+            Stats::Tcp(s) => s.restore_bucket(resolution, bucket),
+        }
+    }
+
+    pub fn restore_change(&mut self, change: StatusChange) {
+        match self {
+            Stats::Ping(s) => s.restore_change(change),
+            Stats::Http(s) => s.restore_change(change),
+            Stats::Tcp(s) => s.restore_change(change),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PingStatus {
     NotStarted,
     Active,
@@ -32,11 +160,17 @@ pub struct PingStats {
     pub avg_latency: Option<Duration>,
     pub min_latency: Option<Duration>,
     pub max_latency: Option<Duration>,
+    /// Percentiles, standard deviation and jitter of recent latencies
+    pub spread: Option<LatencySpread>,
     pub packets_sent: u64,
     pub packets_received: u64,
     pub packet_loss_percent: f64,
     pub last_updated: DateTime<Local>,
-    recent_latencies: VecDeque<Duration>,
+    recent_latencies: LatencyWindow,
+    /// Recent results in order, `None` for timeouts
+    recent_results: VecDeque<Option<Duration>>,
+    timeline: Timeline,
+    status_log: StatusLog,
     consecutive_timeouts: u32,
 }
 
@@ -48,11 +182,15 @@ impl Default for PingStats {
             avg_latency: None,
             min_latency: None,
             max_latency: None,
+            spread: None,
             packets_sent: 0,
             packets_received: 0,
             packet_loss_percent: 0.0,
             last_updated: Local::now(),
-            recent_latencies: VecDeque::with_capacity(MAX_SAMPLES),
+            recent_latencies: LatencyWindow::new(MAX_SAMPLES),
+            recent_results: VecDeque::with_capacity(MAX_SAMPLES),
+            timeline: Timeline::default(),
+            status_log: StatusLog::default(),
             consecutive_timeouts: 0,
         }
     }
@@ -65,9 +203,15 @@ impl PingStats {
 
     /// Update statistics with a new ping result
     pub fn update(&mut self, result: Option<Duration>) {
+        let previous = self.status;
         self.packets_sent += 1;
         self.last_updated = Local::now();
 
+        if self.recent_results.len() >= MAX_SAMPLES {
+            self.recent_results.pop_front();
+        }
+        self.recent_results.push_back(result);
+
         match result {
             Some(latency) => {
                 self.packets_received += 1;
@@ -76,10 +220,7 @@ impl PingStats {
                 self.status = PingStatus::Active;
 
                 // Add to recent latencies (ring buffer)
-                if self.recent_latencies.len() >= MAX_SAMPLES {
-                    self.recent_latencies.pop_front();
-                }
-                self.recent_latencies.push_back(latency);
+                self.recent_latencies.push(latency);
 
                 // Calculate statistics
                 self.calculate_stats();
@@ -103,6 +244,64 @@ impl PingStats {
                 ((self.packets_sent - self.packets_received) as f64 / self.packets_sent as f64)
                     * 100.0;
         }
+
+        let detail = ResultDetail {
+            status: Some(ProbeStatus::Ping(self.status)),
+            success: result.is_some(),
+            ..ResultDetail::default()
+        };
+        self.timeline.push(self.last_updated, result, detail, None);
+        self.status_log.record(self.last_updated, &previous, &self.status);
+    }
+
+    /// Replay a ping result from an earlier session; see `Stats::restore`
+    pub fn restore(&mut self, at: DateTime<Local>, result: Option<Duration>, detail: ResultDetail) {
+        self.packets_sent += 1;
+        if self.recent_results.len() >= MAX_SAMPLES {
+            self.recent_results.pop_front();
+        }
+        self.recent_results.push_back(result);
+        self.timeline.restore(at, result, detail);
+
+        if let Some(latency) = result {
+            self.packets_received += 1;
+            self.recent_latencies.push(latency);
+            self.calculate_stats();
+        }
+        self.packet_loss_percent =
+            ((self.packets_sent - self.packets_received) as f64 / self.packets_sent as f64) * 100.0;
+    }
+
+    /// See `Stats::restore_counts`
+    pub fn restore_counts(&mut self, sent: u64, received: u64) {
+        self.packets_sent += sent;
+        self.packets_received += received;
+        if self.packets_sent > 0 {
+            self.packet_loss_percent = ((self.packets_sent - self.packets_received) as f64
+                / self.packets_sent as f64)
+                * 100.0;
+        }
+    }
+
+    pub fn restore_bucket(&mut self, resolution: Resolution, bucket: Bucket) {
+        self.timeline.restore_bucket(resolution, bucket);
+    }
+
+    pub fn restore_change(&mut self, change: StatusChange) {
+        self.status_log.restore(change);
+    }
+
+    /// The last `MAX_SAMPLES` results, oldest first, with `None` for timeouts
+    pub fn history(&self) -> impl Iterator<Item = Option<Duration>> + '_ {
+        self.recent_results.iter().copied()
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn status_log(&self) -> &StatusLog {
+        &self.status_log
     }
 
     fn calculate_stats(&mut self) {
@@ -110,12 +309,9 @@ impl PingStats {
             return;
         }
 
-        // Calculate average
-        let sum: Duration = self.recent_latencies.iter().sum();
-        self.avg_latency = Some(sum / self.recent_latencies.len() as u32);
-
-        // Calculate min/max
-        self.min_latency = self.recent_latencies.iter().min().copied();
-        self.max_latency = self.recent_latencies.iter().max().copied();
+        self.avg_latency = self.recent_latencies.mean();
+        self.min_latency = self.recent_latencies.min();
+        self.max_latency = self.recent_latencies.max();
+        self.spread = self.recent_latencies.spread();
     }
 }
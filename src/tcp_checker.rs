@@ -0,0 +1,125 @@
+use crate::config::ProbeConfig;
+use crate::stats::{Probe, Stats, StatsMap};
+use crate::tcp_stats::TcpOutcome;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// Start an async TCP connect task for a specific IP address and port
+pub async fn start_tcp_task(
+    ip: IpAddr,
+    port: u16,
+    config: ProbeConfig,
+    stats: StatsMap,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let outcome = probe_port(SocketAddr::new(ip, port), config.timeout).await;
+
+                // Update statistics
+                {
+                    let mut stats_lock = stats.write();
+                    if let Some(Stats::Tcp(host_stats)) = stats_lock.get_mut(&(ip, Probe::Tcp(port))) {
+                        host_stats.update(outcome);
+                    }
+                }
+            }
+            _ = shutdown.changed() => {
+                // Graceful shutdown
+                break;
+            }
+        }
+    }
+}
+
+/// Measure the time to complete a TCP handshake, distinguishing refused from filtered ports
+async fn probe_port(addr: SocketAddr, timeout: Duration) -> TcpOutcome {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        // The stream is dropped immediately, closing the connection
+        Ok(Ok(_stream)) => TcpOutcome::Connected(start.elapsed()),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => TcpOutcome::Refused,
+        Ok(Err(e)) if e.kind() == ErrorKind::TimedOut => TcpOutcome::Timeout,
+        Ok(Err(e)) => TcpOutcome::Error(e.to_string()),
+        Err(_) => TcpOutcome::Timeout,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::AppMode;
+    use crate::tcp_stats::TcpStatus;
+    use tokio::net::TcpListener;
+    use tokio::sync::watch;
+
+    /// A loopback port nothing listens on
+    async fn closed_port() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn tells_open_from_refused_ports() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        let outcome = probe_port(open, Duration::from_secs(2)).await;
+        assert!(matches!(outcome, TcpOutcome::Connected(t) if t < Duration::from_secs(2)), "{:?}", outcome);
+
+        let outcome = probe_port(closed_port().await, Duration::from_secs(2)).await;
+        assert!(matches!(outcome, TcpOutcome::Refused), "{:?}", outcome);
+    }
+
+    #[tokio::test]
+    async fn task_records_each_outcome_for_its_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let targets = [listener.local_addr().unwrap(), closed_port().await];
+        let stats = StatsMap::default();
+        let (shutdown, watcher) = watch::channel(false);
+        let config = ProbeConfig {
+            interval: Duration::from_millis(50),
+            ..ProbeConfig::defaults(AppMode::Tcp)
+        };
+        for addr in targets {
+            let probe = Probe::Tcp(addr.port());
+            stats.write().insert((addr.ip(), probe), probe.new_stats());
+            tokio::spawn(start_tcp_task(
+                addr.ip(),
+                addr.port(),
+                config.clone(),
+                stats.clone(),
+                watcher.clone(),
+            ));
+        }
+
+        let attempted = |stats: &StatsMap| {
+            stats.read().values().all(|s| matches!(s, Stats::Tcp(t) if t.attempts >= 2))
+        };
+        for _ in 0..100 {
+            if attempted(&stats) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = shutdown.send(true);
+
+        let stats_lock = stats.read();
+        let status = |addr: &SocketAddr| match stats_lock.get(&(addr.ip(), Probe::Tcp(addr.port()))) {
+            Some(Stats::Tcp(t)) => (t.status, t.successful, t.last_error.clone()),
+            other => panic!("unexpected stats {:?}", other),
+        };
+        let (open_status, open_successes, open_error) = status(&targets[0]);
+        assert_eq!(open_status, TcpStatus::Open);
+        assert!(open_successes >= 2);
+        assert_eq!(open_error, None);
+        let (closed_status, closed_successes, closed_error) = status(&targets[1]);
+        assert_eq!(closed_status, TcpStatus::Refused);
+        assert_eq!(closed_successes, 0);
+        assert_eq!(closed_error.as_deref(), Some("Connection refused"));
+    }
+}
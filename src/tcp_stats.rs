@@ -0,0 +1,186 @@
+use crate::history::{Bucket, Resolution, ResultDetail, StatusChange, StatusLog, Timeline};
+use crate::stats::ProbeStatus;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const MAX_SAMPLES: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TcpStatus {
+    NotStarted,
+    Open,      // Handshake completed
+    Refused,   // RST received (host up, port closed)
+    Filtered,  // No answer before the timeout
+    Error,     // Unreachable or other socket errors
+}
+
+/// Result of a single TCP connect attempt
+#[derive(Clone, Debug)]
+pub enum TcpOutcome {
+    Connected(Duration),
+    Refused,
+    Timeout,
+    Error(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TcpStats {
+    pub status: TcpStatus,
+    pub last_connect_time: Option<Duration>,
+    pub avg_connect_time: Option<Duration>,
+    pub min_connect_time: Option<Duration>,
+    pub max_connect_time: Option<Duration>,
+    pub last_error: Option<String>,
+    pub attempts: u64,
+    pub successful: u64,
+    pub refused: u64,
+    pub timeouts: u64,
+    pub success_rate_percent: f64,
+    pub last_updated: DateTime<Local>,
+    recent_times: VecDeque<Duration>,
+    timeline: Timeline,
+    status_log: StatusLog,
+}
+
+impl Default for TcpStats {
+    fn default() -> Self {
+        Self {
+            status: TcpStatus::NotStarted,
+            last_connect_time: None,
+            avg_connect_time: None,
+            min_connect_time: None,
+            max_connect_time: None,
+            last_error: None,
+            attempts: 0,
+            successful: 0,
+            refused: 0,
+            timeouts: 0,
+            success_rate_percent: 0.0,
+            last_updated: Local::now(),
+            recent_times: VecDeque::with_capacity(MAX_SAMPLES),
+            timeline: Timeline::default(),
+            status_log: StatusLog::default(),
+        }
+    }
+}
+
+impl TcpStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update statistics with a new connect result
+    pub fn update(&mut self, outcome: TcpOutcome) {
+        let previous = self.status;
+        self.attempts += 1;
+        self.last_updated = Local::now();
+
+        let sample = match outcome {
+            TcpOutcome::Connected(connect_time) => Some(connect_time),
+            _ => None,
+        };
+
+        self.status = match outcome {
+            TcpOutcome::Connected(connect_time) => {
+                self.successful += 1;
+                self.last_connect_time = Some(connect_time);
+                self.last_error = None;
+
+                // Add to recent times (ring buffer)
+                if self.recent_times.len() >= MAX_SAMPLES {
+                    self.recent_times.pop_front();
+                }
+                self.recent_times.push_back(connect_time);
+
+                // Calculate statistics
+                self.calculate_stats();
+                TcpStatus::Open
+            }
+            TcpOutcome::Refused => {
+                self.refused += 1;
+                self.last_connect_time = None;
+                self.last_error = Some("Connection refused".to_string());
+                TcpStatus::Refused
+            }
+            TcpOutcome::Timeout => {
+                self.timeouts += 1;
+                self.last_connect_time = None;
+                self.last_error = Some("Connect timeout".to_string());
+                TcpStatus::Filtered
+            }
+            TcpOutcome::Error(e) => {
+                self.last_connect_time = None;
+                self.last_error = Some(e);
+                TcpStatus::Error
+            }
+        };
+
+        // Calculate success rate
+        if self.attempts > 0 {
+            self.success_rate_percent = (self.successful as f64 / self.attempts as f64) * 100.0;
+        }
+
+        let detail = ResultDetail {
+            status: Some(ProbeStatus::Tcp(self.status)),
+            success: sample.is_some(),
+            ..ResultDetail::default()
+        };
+        self.timeline.push(self.last_updated, sample, detail, self.last_error.as_deref());
+        self.status_log.record(self.last_updated, &previous, &self.status);
+    }
+
+    /// Replay a connect result from an earlier session; see `Stats::restore`
+    pub fn restore(&mut self, at: DateTime<Local>, sample: Option<Duration>, detail: ResultDetail) {
+        self.attempts += 1;
+        self.timeline.restore(at, sample, detail);
+        if let Some(connect_time) = sample {
+            self.successful += 1;
+            if self.recent_times.len() >= MAX_SAMPLES {
+                self.recent_times.pop_front();
+            }
+            self.recent_times.push_back(connect_time);
+            self.calculate_stats();
+        }
+        self.success_rate_percent = (self.successful as f64 / self.attempts as f64) * 100.0;
+    }
+
+    /// See `Stats::restore_counts`
+    pub fn restore_counts(&mut self, attempts: u64, successful: u64) {
+        self.attempts += attempts;
+        self.successful += successful;
+        if self.attempts > 0 {
+            self.success_rate_percent = (self.successful as f64 / self.attempts as f64) * 100.0;
+        }
+    }
+
+    pub fn restore_bucket(&mut self, resolution: Resolution, bucket: Bucket) {
+        self.timeline.restore_bucket(resolution, bucket);
+    }
+
+    pub fn restore_change(&mut self, change: StatusChange) {
+        self.status_log.restore(change);
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn status_log(&self) -> &StatusLog {
+        &self.status_log
+    }
+
+    fn calculate_stats(&mut self) {
+        if self.recent_times.is_empty() {
+            return;
+        }
+
+        // Calculate average
+        let sum: Duration = self.recent_times.iter().sum();
+        self.avg_connect_time = Some(sum / self.recent_times.len() as u32);
+
+        // Calculate min/max
+        self.min_connect_time = self.recent_times.iter().min().copied();
+        self.max_connect_time = self.recent_times.iter().max().copied();
+    }
+}
@@ -1,22 +1,52 @@
-use crate::app::App;
-use crate::http_stats::HttpStatus;
-use crate::stats::{AppMode, PingStatus, Stats};
+use crate::app::{App, Host, HostEvent};
+use crate::history::{Resolution, Timeline};
+use crate::http_stats::{CertInfo, HttpStatus, HttpTimings};
+use crate::latency::LatencySpread;
+use crate::messages::{Level, Message};
+use crate::stats::{AppMode, PingStatus, Probe, Stats};
+use crate::tcp_stats::TcpStatus;
+use chrono::{DateTime, Local};
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Constraint, Direction, Flex, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table},
+    symbols::Marker,
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, List, ListItem, Paragraph,
+        Row, Sparkline, Table, Wrap,
+    },
     Frame,
 };
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 pub fn render(app: &App, frame: &mut Frame) {
-    let size = frame.area();
+    let mut size = frame.area();
 
-    // Create main layout: hosts on left, stats on right
+    // Alert banner across the top while anything is firing or just recovered
+    if app.alerts.active().next().is_some() || app.alerts.recent_recoveries().next().is_some() {
+        let [banner, rest] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(size);
+        render_alert_banner(app, frame, banner);
+        size = rest;
+    }
+
+    if app.show_detail {
+        if let Some(host) = app.hosts.get(app.selected_index) {
+            render_host_detail(app, host, frame, size);
+            render_messages(app, frame, size);
+            return;
+        }
+    }
+
+    // Create main layout: hosts on left, stats on right, event log below
+    let events_height = if app.show_events { EVENTS_HEIGHT } else { 0 };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(events_height),
+            Constraint::Length(3),
+        ])
         .split(size);
 
     let main_chunks = Layout::default()
@@ -26,12 +56,109 @@ pub fn render(app: &App, frame: &mut Frame) {
 
     render_host_list(app, frame, main_chunks[0]);
 
-    match app.mode {
-        AppMode::Icmp => render_ping_stats_panel(app, frame, main_chunks[1]),
-        AppMode::Http => render_http_stats_panel(app, frame, main_chunks[1]),
+    // One stats table per probe kind in use, stacked vertically
+    let modes = app.active_modes();
+    let stats_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Ratio(1, modes.len().max(1) as u32); modes.len()])
+        .split(main_chunks[1]);
+
+    for (mode, area) in modes.iter().zip(stats_chunks.iter()) {
+        match mode {
+            AppMode::Icmp => render_ping_stats_panel(app, frame, *area),
+            AppMode::Http if app.show_http_timings => render_http_timings_panel(app, frame, *area),
+            AppMode::Http => render_http_stats_panel(app, frame, *area),
+            AppMode::Tcp => render_tcp_stats_panel(app, frame, *area),
+        }
+    }
+
+    if app.show_events {
+        render_events_panel(app, frame, chunks[1]);
+    }
+
+    render_help(app, frame, chunks[2]);
+
+    if app.show_status_codes {
+        render_status_codes_popup(app, frame, size);
+    }
+    render_messages(app, frame, size);
+}
+
+/// The message log popup when open, otherwise the current toast
+fn render_messages(app: &App, frame: &mut Frame, area: Rect) {
+    if app.show_log {
+        render_log_popup(app, frame, area);
+    } else if let Some(message) = app.messages.current_toast() {
+        render_toast(message, frame, area);
+    }
+}
+
+/// Bottom-right box just above the key help, wrapped to at most half the screen
+fn render_toast(message: &Message, frame: &mut Frame, area: Rect) {
+    let length = message.text.chars().count() as u16;
+    let width = (length + 4).clamp(20, (area.width / 2).max(20)).min(area.width);
+    // One spare line for words wrapped early
+    let lines = length.div_ceil(width.saturating_sub(2).max(1)) + u16::from(length + 2 > width);
+    let height = (lines + 2).min(area.height.saturating_sub(3));
+    let toast = Rect {
+        x: area.right().saturating_sub(width),
+        y: area.bottom().saturating_sub(height + 3),
+        width,
+        height,
+    };
+
+    let style = message_style(message.level);
+    let paragraph = Paragraph::new(message.text.as_str())
+        .style(style)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).border_style(style));
+
+    frame.render_widget(Clear, toast);
+    frame.render_widget(paragraph, toast);
+}
+
+/// Everything reported by tasks, exports and hooks, newest first
+fn render_log_popup(app: &App, frame: &mut Frame, area: Rect) {
+    let entries = app.messages.entries();
+    let count = entries.len();
+    let scroll = app.messages.scroll.min(count.saturating_sub(1));
+
+    let mut items: Vec<ListItem> = entries
+        .rev()
+        .skip(scroll)
+        .map(|message| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    message.at.format("%H:%M:%S ").to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{:<8}", message.level.as_str()), message_style(message.level)),
+                Span::raw(message.text.clone()),
+            ]))
+        })
+        .collect();
+    if items.is_empty() {
+        items.push(ListItem::new("No messages"));
     }
 
-    render_help(app, frame, chunks[1]);
+    let title = if scroll > 0 {
+        format!("Messages ({}, {} newer above, Home for latest) - l/Esc to close", count, scroll)
+    } else {
+        format!("Messages ({}) - PgUp/PgDn to scroll, l/Esc to close", count)
+    };
+    let popup = centered_rect(80, 60, area);
+    let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(list, popup);
+}
+
+fn message_style(level: Level) -> Style {
+    match level {
+        Level::Info => Style::default().fg(Color::Green),
+        Level::Warning => Style::default().fg(Color::Yellow),
+        Level::Error => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+    }
 }
 
 fn render_host_list(app: &App, frame: &mut Frame, area: Rect) {
@@ -42,14 +169,25 @@ fn render_host_list(app: &App, frame: &mut Frame, area: Rect) {
         .map(|(i, host)| {
             let checkbox = if host.selected { "[x]" } else { "[ ]" };
             let emoji = if host.selected { " 🔌" } else { "   " };
-            let content = format!("{} {}{}", checkbox, host.ip, emoji);
+            let family = if host.ip.is_ipv4() { "v4" } else { "v6" };
+            let content = match app.hostname(&host.ip) {
+                Some(name) => format!("{} {} {} ({}){}", checkbox, family, host.ip, name, emoji),
+                None => format!("{} {} {}{}", checkbox, family, host.ip, emoji),
+            };
 
-            let style = if i == app.selected_index {
+            // Dim hosts nmap reported as down
+            let base_style = if host.scan.state.as_deref() == Some("down") {
+                Style::default().fg(Color::Gray)
+            } else {
                 Style::default()
+            };
+
+            let style = if i == app.selected_index {
+                base_style
                     .bg(Color::DarkGray)
                     .add_modifier(Modifier::BOLD)
             } else {
-                Style::default()
+                base_style
             };
 
             ListItem::new(content).style(style)
@@ -76,78 +214,252 @@ fn render_host_list(app: &App, frame: &mut Frame, area: Rect) {
 fn render_ping_stats_panel(app: &App, frame: &mut Frame, area: Rect) {
     let stats_lock = app.stats.read();
 
-    // Build table rows for selected hosts
-    let rows: Vec<Row> = app
-        .hosts
-        .iter()
-        .filter(|h| h.selected)
-        .filter_map(|host| {
-            if let Some(Stats::Ping(stats)) = stats_lock.get(&host.ip) {
-                let status_style = match stats.status {
-                    PingStatus::Active => Style::default().fg(Color::Green),
-                    PingStatus::Timeout => Style::default().fg(Color::Red),
-                    PingStatus::Unreachable => Style::default().fg(Color::Yellow),
-                    PingStatus::NotStarted => Style::default().fg(Color::Gray),
-                };
+    // Build table rows for each probe of the selected hosts, plus latency history for sparklines
+    let (rows, histories): (Vec<Row>, Vec<Vec<Option<Duration>>>) = app
+        .selected_probes(AppMode::Icmp)
+        .filter_map(|(host, probe)| {
+            if let Some(Stats::Ping(stats)) = stats_lock.get(&(host.ip, probe)) {
+                let status_style = ping_status_style(&stats.status);
 
-                Some(Row::new(vec![
+                let mut cells = vec![
                     host.ip.to_string(),
                     format_ping_status(&stats.status),
                     format_duration(stats.last_latency),
                     format_duration(stats.avg_latency),
+                    String::new(),
                     format!("{:.1}%", stats.packet_loss_percent),
                     format!("{}/{}", stats.packets_received, stats.packets_sent),
-                ])
-                .style(status_style))
+                ];
+                if app.show_latency_spread {
+                    cells.extend(format_spread(stats.spread.as_ref()));
+                }
+
+                Some((Row::new(cells).style(status_style), stats.history().collect()))
             } else {
                 None
             }
         })
-        .collect();
-
-    let header = Row::new(vec!["IP", "Status", "Last", "Avg", "Loss", "Packets"])
-        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+        .unzip();
 
-    let widths = [
+    let mut header = vec!["IP", "Status", "Last", "Avg", "History", "Loss", "Packets"];
+    let mut widths = vec![
         Constraint::Length(20),
         Constraint::Length(12),
         Constraint::Length(10),
         Constraint::Length(10),
+        Constraint::Length(HISTORY_WIDTH),
         Constraint::Length(10),
         Constraint::Length(12),
     ];
+    if app.show_latency_spread {
+        header.extend(SPREAD_HEADERS);
+        widths.extend([Constraint::Length(10); SPREAD_HEADERS.len()]);
+    }
+    let header = Row::new(header)
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
 
-    let table = Table::new(rows, widths)
+    let table = Table::new(rows, widths.clone())
         .header(header)
         .block(Block::default().title("Ping Statistics").borders(Borders::ALL));
 
     frame.render_widget(table, area);
+    render_sparkline_column(frame, area, &widths, HISTORY_COLUMN, &histories);
 }
 
 fn render_http_stats_panel(app: &App, frame: &mut Frame, area: Rect) {
     let stats_lock = app.stats.read();
 
-    // Build table rows for selected hosts
+    // Build table rows for each probe of the selected hosts, plus response history for sparklines
+    let (rows, histories): (Vec<Row>, Vec<Vec<Option<Duration>>>) = app
+        .selected_probes(AppMode::Http)
+        .filter_map(|(host, probe)| {
+            if let Some(Stats::Http(stats)) = stats_lock.get(&(host.ip, probe)) {
+                let status_style = http_status_style(&stats.status);
+
+                let mut cells = vec![
+                    Cell::from(format_target(host.ip, probe)),
+                    Cell::from(format_http_status(stats.last_status_code, &stats.status)),
+                    Cell::from(format_duration(stats.last_response_time)),
+                    Cell::from(format_duration(stats.avg_response_time)),
+                    Cell::from(""),
+                    Cell::from(format!(
+                        "{}{}",
+                        if stats.content_truncated { ">" } else { "" },
+                        format_size(stats.last_content_size)
+                    )),
+                    format_changes_cell(stats.content_changes, stats.last_content_change),
+                    format_cert_cell(stats.cert.as_ref()),
+                    Cell::from(format_error(&stats.last_error)),
+                ];
+                if app.show_latency_spread {
+                    cells.extend(format_spread(stats.spread.as_ref()).into_iter().map(Cell::from));
+                }
+
+                Some((Row::new(cells).style(status_style), stats.history().collect()))
+            } else {
+                None
+            }
+        })
+        .unzip();
+
+    let mut header =
+        vec!["Target", "Status", "Last", "Avg", "History", "Size", "Changed", "Cert", "Error"];
+    let mut widths = vec![
+        Constraint::Length(26),  // Target
+        Constraint::Length(12),  // Status
+        Constraint::Length(10),  // Last
+        Constraint::Length(10),  // Avg
+        Constraint::Length(HISTORY_WIDTH), // History
+        Constraint::Length(10),  // Size
+        Constraint::Length(14),  // Changed
+        Constraint::Length(8),   // Cert
+        Constraint::Length(30),  // Error
+    ];
+    if app.show_latency_spread {
+        header.extend(SPREAD_HEADERS);
+        widths.extend([Constraint::Length(10); SPREAD_HEADERS.len()]);
+    }
+    let header = Row::new(header)
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+
+    let table = Table::new(rows, widths.clone())
+        .header(header)
+        .block(Block::default().title("HTTP Statistics").borders(Borders::ALL));
+
+    frame.render_widget(table, area);
+    render_sparkline_column(frame, area, &widths, HISTORY_COLUMN, &histories);
+}
+
+/// Width of the sparkline column, i.e. how many recent samples are drawn
+const HISTORY_WIDTH: u16 = 20;
+
+/// Position of the History column in the ICMP and HTTP tables
+const HISTORY_COLUMN: usize = 4;
+
+/// Draw one sparkline per row into a column of a table already rendered in `area`.
+///
+/// Table cells only hold text, so the sparklines are drawn over the empty column
+/// using the same layout the table uses. Missing samples (timeouts, network
+/// errors) are marked with a red `×` instead of a bar.
+fn render_sparkline_column(
+    frame: &mut Frame,
+    area: Rect,
+    widths: &[Constraint],
+    column: usize,
+    histories: &[Vec<Option<Duration>>],
+) {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    let columns = Layout::horizontal(widths.iter().copied())
+        .flex(Flex::Start)
+        .spacing(1)
+        .split(inner);
+    let Some(column) = columns.get(column) else {
+        return;
+    };
+
+    // Rows start below the header line
+    for (i, history) in histories.iter().enumerate() {
+        let y = inner.y + 1 + i as u16;
+        if y >= inner.bottom() {
+            break;
+        }
+
+        let recent = &history[history.len().saturating_sub(column.width as usize)..];
+        let data: Vec<u64> = recent
+            .iter()
+            .map(|sample| sample.map_or(0, |d| d.as_micros() as u64))
+            .collect();
+        let rect = Rect::new(column.x, y, column.width, 1);
+
+        frame.render_widget(
+            Sparkline::default()
+                .data(&data)
+                .max(data.iter().copied().max().unwrap_or(0).max(1))
+                .style(Style::default().fg(Color::Cyan)),
+            rect,
+        );
+
+        let buf = frame.buffer_mut();
+        for (x, sample) in (rect.x..).zip(recent) {
+            if sample.is_none() {
+                buf[(x, y)]
+                    .set_symbol("×")
+                    .set_style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD));
+            }
+        }
+    }
+}
+
+/// Where HTTP time goes, as "last / avg" per phase
+fn render_http_timings_panel(app: &App, frame: &mut Frame, area: Rect) {
+    let stats_lock = app.stats.read();
+
     let rows: Vec<Row> = app
-        .hosts
-        .iter()
-        .filter(|h| h.selected)
-        .filter_map(|host| {
-            if let Some(Stats::Http(stats)) = stats_lock.get(&host.ip) {
-                let status_style = match stats.status {
-                    HttpStatus::Success => Style::default().fg(Color::Green),
-                    HttpStatus::ClientError => Style::default().fg(Color::Yellow),
-                    HttpStatus::ServerError | HttpStatus::NetworkError =>
-                        Style::default().fg(Color::Red),
-                    HttpStatus::NotStarted => Style::default().fg(Color::Gray),
+        .selected_probes(AppMode::Http)
+        .filter_map(|(host, probe)| {
+            if let Some(Stats::Http(stats)) = stats_lock.get(&(host.ip, probe)) {
+                let last = stats.last_timings;
+                let avg = stats.avg_timings;
+                let phase = |f: fn(&HttpTimings) -> Option<Duration>| {
+                    format!(
+                        "{} / {}",
+                        format_duration(last.as_ref().and_then(f)),
+                        format_duration(avg.as_ref().and_then(f))
+                    )
                 };
 
                 Some(Row::new(vec![
-                    host.ip.to_string(),
-                    format_http_status(stats.last_status_code, &stats.status),
-                    format_duration(stats.last_response_time),
-                    format_duration(stats.avg_response_time),
-                    format_size(stats.last_content_size),
+                    format_target(host.ip, probe),
+                    phase(|t| Some(t.connect)),
+                    phase(|t| t.tls),
+                    phase(|t| Some(t.ttfb)),
+                    phase(|t| Some(t.transfer)),
+                    phase(|t| Some(t.total)),
+                ]))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let header = Row::new(vec!["Target", "Connect", "TLS", "TTFB", "Transfer", "Total"])
+        .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+
+    let widths = [
+        Constraint::Length(26),  // Target
+        Constraint::Length(19),  // Connect
+        Constraint::Length(19),  // TLS
+        Constraint::Length(19),  // TTFB
+        Constraint::Length(19),  // Transfer
+        Constraint::Length(19),  // Total
+    ];
+
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .title("HTTP Timings (last / avg, t to toggle)")
+            .borders(Borders::ALL),
+    );
+
+    frame.render_widget(table, area);
+}
+
+fn render_tcp_stats_panel(app: &App, frame: &mut Frame, area: Rect) {
+    let stats_lock = app.stats.read();
+
+    // Build table rows for each probe of the selected hosts
+    let rows: Vec<Row> = app
+        .selected_probes(AppMode::Tcp)
+        .filter_map(|(host, probe)| {
+            if let Some(Stats::Tcp(stats)) = stats_lock.get(&(host.ip, probe)) {
+                let status_style = tcp_status_style(&stats.status);
+
+                Some(Row::new(vec![
+                    format_target(host.ip, probe),
+                    format_tcp_status(&stats.status),
+                    format_duration(stats.last_connect_time),
+                    format_duration(stats.avg_connect_time),
+                    format!("{:.1}%", stats.success_rate_percent),
+                    format!("{}/{}", stats.refused, stats.timeouts),
                     format_error(&stats.last_error),
                 ])
                 .style(status_style))
@@ -157,31 +469,510 @@ fn render_http_stats_panel(app: &App, frame: &mut Frame, area: Rect) {
         })
         .collect();
 
-    let header = Row::new(vec!["IP", "Status", "Last", "Avg", "Size", "Error"])
+    let header = Row::new(vec!["Target", "Status", "Last", "Avg", "Success", "Ref/TO", "Error"])
         .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
 
     let widths = [
-        Constraint::Length(20),  // IP
-        Constraint::Length(12),  // Status
+        Constraint::Length(26),  // Target
+        Constraint::Length(10),  // Status
         Constraint::Length(10),  // Last
         Constraint::Length(10),  // Avg
-        Constraint::Length(10),  // Size
+        Constraint::Length(9),   // Success
+        Constraint::Length(8),   // Refused/Timeouts
         Constraint::Length(30),  // Error
     ];
 
     let table = Table::new(rows, widths)
         .header(header)
-        .block(Block::default().title("HTTP Statistics").borders(Borders::ALL));
+        .block(Block::default().title("TCP Connect Statistics").borders(Borders::ALL));
 
     frame.render_widget(table, area);
 }
 
-fn render_help(app: &App, frame: &mut Frame, area: Rect) {
-    let mode_text = match app.mode {
-        AppMode::Icmp => "ICMP".to_string(),
-        AppMode::Http => format!("HTTP:{}", app.port),
+/// Status-code distribution of every HTTP probe on the highlighted host
+fn render_status_codes_popup(app: &App, frame: &mut Frame, area: Rect) {
+    let Some(host) = app.hosts.get(app.selected_index) else {
+        return;
     };
 
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let stats_lock = app.stats.read();
+    let mut lines: Vec<Line> = Vec::new();
+
+    for probe in host.probes.iter().filter(|p| p.mode() == AppMode::Http) {
+        let Some(Stats::Http(stats)) = stats_lock.get(&(host.ip, *probe)) else {
+            continue;
+        };
+
+        lines.push(Line::from(Span::styled(format_target(host.ip, *probe), bold)));
+        lines.push(Line::from(format!(
+            "  {} requests, {} successful ({:.1}%), {} network errors",
+            stats.requests_sent,
+            stats.requests_successful,
+            stats.success_rate_percent,
+            stats.network_errors
+        )));
+        lines.push(Line::from(
+            (1..=5)
+                .map(|class| format!("{}xx: {}", class, stats.class_count(class)))
+                .collect::<Vec<_>>()
+                .join("  "),
+        ));
+
+        for (code, count) in &stats.status_counts {
+            let percent = *count as f64 / stats.requests_sent.max(1) as f64 * 100.0;
+            let reason = reqwest::StatusCode::from_u16(*code)
+                .ok()
+                .and_then(|c| c.canonical_reason())
+                .unwrap_or("");
+            lines.push(Line::from(format!(
+                "  {} {:<24} {:>8} {:>6.1}%",
+                code, reason, count, percent
+            )));
+        }
+        lines.push(Line::from(""));
+    }
+
+    if lines.is_empty() {
+        lines.push(Line::from("No HTTP probes on this host"));
+    }
+
+    let popup = centered_rect(60, 60, area);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!("Status codes: {} (c/Esc to close)", host.ip))
+            .borders(Borders::ALL),
+    );
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(paragraph, popup);
+}
+
+/// Firing alerts in red, then recent recoveries in green, on one line
+fn render_alert_banner(app: &App, frame: &mut Frame, area: Rect) {
+    let firing: Vec<String> = app
+        .alerts
+        .active()
+        .map(|a| format!("{} {} {} [{}]", a.ip, a.probe, a.message, a.rule))
+        .collect();
+    let recovered: Vec<String> = app
+        .alerts
+        .recent_recoveries()
+        .map(|e| match e.duration {
+            Some(d) => format!("{} {} {} after {}", e.ip, e.probe, e.rule, format_elapsed(d)),
+            None => format!("{} {} {}", e.ip, e.probe, e.rule),
+        })
+        .collect();
+
+    let mut spans = Vec::new();
+    if !firing.is_empty() {
+        spans.push(Span::styled(
+            format!(" ⚠ {} ALERT{}: {} ", firing.len(), if firing.len() == 1 { "" } else { "S" }, firing.join(" | ")),
+            Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if !recovered.is_empty() {
+        spans.push(Span::styled(
+            format!(" ✓ recovered: {} ", recovered.join(" | ")),
+            Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD),
+        ));
+    }
+
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Height of the event log pane, borders included
+const EVENTS_HEIGHT: u16 = 8;
+
+/// Status changes of every probe, newest first, scrolled with PageUp/PageDown
+fn render_events_panel(app: &App, frame: &mut Frame, area: Rect) {
+    let events = app.events();
+    let scroll = app.events_scroll.min(events.len().saturating_sub(1));
+
+    let items: Vec<ListItem> = events
+        .iter()
+        .skip(scroll)
+        .map(|event| ListItem::new(format_event(app, event, true)))
+        .collect();
+
+    let title = if scroll > 0 {
+        format!("Events ({}, {} newer above, Home for latest)", events.len(), scroll)
+    } else {
+        format!("Events ({})", events.len())
+    };
+    let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+
+    frame.render_widget(list, area);
+}
+
+/// Full-screen view of one host: latency chart, per-probe stats, nmap metadata and status changes
+fn render_host_detail(app: &App, host: &Host, frame: &mut Frame, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(10), Constraint::Length(16), Constraint::Length(3)])
+        .split(area);
+
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(40),
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+        ])
+        .split(chunks[1]);
+
+    render_latency_chart(app, host, frame, chunks[0]);
+    render_detail_stats(app, host, frame, bottom[0]);
+    render_scan_info(app, host, frame, bottom[1]);
+    render_host_events(app, host, frame, bottom[2]);
+    render_detail_help(app, frame, chunks[2]);
+}
+
+/// Line colors for the probes of a host; red is kept for loss markers
+const CHART_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Yellow,
+    Color::LightCyan,
+];
+
+/// Chart points as (seconds relative to now, milliseconds)
+type Segment = Vec<(f64, f64)>;
+
+/// Latency of every probe of the host over the chart window, lost samples marked along the top
+fn render_latency_chart(app: &App, host: &Host, frame: &mut Frame, area: Rect) {
+    let stats_lock = app.stats.read();
+    let now = Local::now();
+    let window = app.chart_window.duration();
+    let window_secs = window.num_seconds() as f64;
+    let resolution = Timeline::resolution_for(window);
+
+    // A loss splits the line into separate segments
+    let mut series: Vec<(Probe, Vec<Segment>)> = Vec::new();
+    let mut losses: Vec<f64> = Vec::new();
+    for probe in &host.probes {
+        let Some(stats) = stats_lock.get(&(host.ip, *probe)) else {
+            continue;
+        };
+
+        let mut segments: Vec<Segment> = vec![Vec::new()];
+        for point in stats.timeline().since(now - window, resolution) {
+            let x = (point.at - now).num_milliseconds() as f64 / 1000.0;
+            if point.lost {
+                losses.push(x);
+            }
+            match point.latency {
+                Some(d) => segments
+                    .last_mut()
+                    .unwrap()
+                    .push((x, d.as_secs_f64() * 1000.0)),
+                None => {
+                    if !segments.last().unwrap().is_empty() {
+                        segments.push(Vec::new());
+                    }
+                }
+            }
+        }
+        series.push((*probe, segments));
+    }
+    drop(stats_lock);
+
+    let max_ms = series
+        .iter()
+        .flat_map(|(_, segments)| segments.iter().flatten())
+        .map(|(_, ms)| *ms)
+        .fold(0.0, f64::max);
+    let y_max = (max_ms * 1.1).max(1.0);
+    let loss_points: Vec<(f64, f64)> = losses.iter().map(|x| (*x, y_max)).collect();
+
+    let mut datasets: Vec<Dataset> = Vec::new();
+    for (i, (probe, segments)) in series.iter().enumerate() {
+        let style = Style::default().fg(CHART_COLORS[i % CHART_COLORS.len()]);
+        for (j, segment) in segments.iter().enumerate() {
+            let mut dataset = Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(style)
+                .data(segment);
+            // One legend entry per probe
+            if j == 0 {
+                dataset = dataset.name(probe.to_string());
+            }
+            datasets.push(dataset);
+        }
+    }
+    if !loss_points.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("loss")
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+                .data(&loss_points),
+        );
+    }
+
+    let label = app.chart_window.label();
+    let x_axis = Axis::default()
+        .bounds([-window_secs, 0.0])
+        .labels([
+            format!("-{}", label),
+            format!("-{}", format_seconds(window_secs / 2.0)),
+            "now".to_string(),
+        ])
+        .style(Style::default().fg(Color::Gray));
+    let y_axis = Axis::default()
+        .title("ms")
+        .bounds([0.0, y_max])
+        .labels([
+            "0".to_string(),
+            format!("{:.1}", y_max / 2.0),
+            format!("{:.1}", y_max),
+        ])
+        .style(Style::default().fg(Color::Gray));
+
+    let span = match resolution {
+        Resolution::Raw => format!("last {}", label),
+        Resolution::Minute => format!("last {}, 1-min averages", label),
+        Resolution::Hour => format!("last {}, 1-hour averages", label),
+    };
+    let title = match app.hostname(&host.ip) {
+        Some(name) => format!("Latency: {} ({}) - {}", host.ip, name, span),
+        None => format!("Latency: {} - {}", host.ip, span),
+    };
+    let chart = Chart::new(datasets)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .x_axis(x_axis)
+        .y_axis(y_axis)
+        .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
+
+    frame.render_widget(chart, area);
+}
+
+/// One probe's column in the detail statistics table
+struct ProbeSummary {
+    probe: Probe,
+    status: Cell<'static>,
+    last: Option<Duration>,
+    avg: Option<Duration>,
+    min: Option<Duration>,
+    max: Option<Duration>,
+    spread: Option<LatencySpread>,
+    loss_percent: f64,
+    count: String,
+}
+
+impl ProbeSummary {
+    fn new(probe: Probe, stats: &Stats) -> Self {
+        match stats {
+            Stats::Ping(s) => Self {
+                probe,
+                status: Cell::from(format_ping_status(&s.status)).style(ping_status_style(&s.status)),
+                last: s.last_latency,
+                avg: s.avg_latency,
+                min: s.min_latency,
+                max: s.max_latency,
+                spread: s.spread,
+                loss_percent: s.packet_loss_percent,
+                count: format!("{}/{}", s.packets_received, s.packets_sent),
+            },
+            Stats::Http(s) => Self {
+                probe,
+                status: Cell::from(format_http_status(s.last_status_code, &s.status))
+                    .style(http_status_style(&s.status)),
+                last: s.last_response_time,
+                avg: s.avg_response_time,
+                min: s.min_response_time,
+                max: s.max_response_time,
+                spread: s.spread,
+                loss_percent: failure_percent(s.requests_sent, s.success_rate_percent),
+                count: format!("{}/{}", s.requests_successful, s.requests_sent),
+            },
+            Stats::Tcp(s) => Self {
+                probe,
+                status: Cell::from(format_tcp_status(&s.status)).style(tcp_status_style(&s.status)),
+                last: s.last_connect_time,
+                avg: s.avg_connect_time,
+                min: s.min_connect_time,
+                max: s.max_connect_time,
+                spread: None,
+                loss_percent: failure_percent(s.attempts, s.success_rate_percent),
+                count: format!("{}/{}", s.successful, s.attempts),
+            },
+        }
+    }
+}
+
+/// Aggregate stats of every probe of the host, one column each
+fn render_detail_stats(app: &App, host: &Host, frame: &mut Frame, area: Rect) {
+    let summaries: Vec<ProbeSummary> = {
+        let stats_lock = app.stats.read();
+        host.probes
+            .iter()
+            .filter_map(|probe| Some(ProbeSummary::new(*probe, stats_lock.get(&(host.ip, *probe))?)))
+            .collect()
+    };
+
+    let row = |name: &'static str, cell: &dyn Fn(&ProbeSummary) -> Cell<'static>| {
+        Row::new(std::iter::once(Cell::from(name)).chain(summaries.iter().map(cell)))
+    };
+    let duration = |d: Option<Duration>| Cell::from(format_duration(d));
+
+    let rows = vec![
+        row("Status", &|p| p.status.clone()),
+        row("Last", &|p| duration(p.last)),
+        row("Avg", &|p| duration(p.avg)),
+        row("Min", &|p| duration(p.min)),
+        row("Max", &|p| duration(p.max)),
+        row("P50", &|p| duration(p.spread.map(|s| s.p50))),
+        row("P90", &|p| duration(p.spread.map(|s| s.p90))),
+        row("P99", &|p| duration(p.spread.map(|s| s.p99))),
+        row("StdDev", &|p| duration(p.spread.map(|s| s.std_dev))),
+        row("Jitter", &|p| duration(p.spread.map(|s| s.jitter))),
+        row("Loss", &|p| Cell::from(format!("{:.1}%", p.loss_percent))),
+        row("OK/Sent", &|p| Cell::from(p.count.clone())),
+    ];
+
+    let header = Row::new(
+        std::iter::once(String::new()).chain(summaries.iter().map(|p| p.probe.to_string())),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+
+    let mut widths = vec![Constraint::Length(8)];
+    widths.extend(vec![Constraint::Length(12); summaries.len()]);
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().title("Statistics").borders(Borders::ALL));
+
+    frame.render_widget(table, area);
+}
+
+/// What nmap reported about the host
+fn render_scan_info(app: &App, host: &Host, frame: &mut Frame, area: Rect) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let scan = &host.scan;
+    let field = |name: &'static str, value: String| {
+        Line::from(vec![Span::styled(name, bold), Span::raw(value)])
+    };
+
+    let hostnames = if scan.hostnames.is_empty() {
+        app.hostname(&host.ip).unwrap_or_else(|| "-".to_string())
+    } else {
+        scan.hostnames.join(", ")
+    };
+    let mac = match (&scan.mac, &scan.mac_vendor) {
+        (Some(mac), Some(vendor)) => format!("{} ({})", mac, vendor),
+        (Some(mac), None) => mac.clone(),
+        (None, _) => "-".to_string(),
+    };
+    let addresses = scan
+        .addresses
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut lines = vec![
+        field("State: ", scan.state.clone().unwrap_or_else(|| "-".to_string())),
+        field("Hostname: ", hostnames),
+        field("Addresses: ", addresses),
+        field("MAC: ", mac),
+        field("Ports: ", if scan.ports.is_empty() { "-".to_string() } else { String::new() }),
+    ];
+    for port in &scan.ports {
+        let service = match (&port.service, &port.tunnel) {
+            (Some(service), Some(tunnel)) => format!(" {}/{}", tunnel, service),
+            (Some(service), None) => format!(" {}", service),
+            (None, _) => String::new(),
+        };
+        lines.push(Line::from(format!(
+            "  {}/{} {}{}",
+            port.port, port.protocol, port.state, service
+        )));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().title("Scan").borders(Borders::ALL));
+
+    frame.render_widget(paragraph, area);
+}
+
+/// Status changes of every probe of the host, newest first
+fn render_host_events(app: &App, host: &Host, frame: &mut Frame, area: Rect) {
+    let events: Vec<HostEvent> = app.events().into_iter().filter(|e| e.ip == host.ip).collect();
+
+    let items: Vec<ListItem> = events
+        .iter()
+        .map(|event| ListItem::new(format_event(app, event, false)))
+        .collect();
+
+    let list = List::new(items).block(
+        Block::default()
+            .title(format!("Events ({})", events.len()))
+            .borders(Borders::ALL),
+    );
+
+    frame.render_widget(list, area);
+}
+
+fn render_detail_help(app: &App, frame: &mut Frame, area: Rect) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let spans = vec![
+        Span::styled("Esc", bold),
+        Span::raw(": back | "),
+        Span::styled("↑↓", bold),
+        Span::raw(": host | "),
+        Span::styled("w", bold),
+        Span::raw(": window ("),
+        Span::styled(app.chart_window.label(), bold.fg(Color::Cyan)),
+        Span::raw(") | "),
+        Span::styled("q", bold),
+        Span::raw(": quit"),
+    ];
+
+    let paragraph = Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL));
+
+    frame.render_widget(paragraph, area);
+}
+
+/// A rectangle of the given percentage size centered in `area`
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn render_help(app: &App, frame: &mut Frame, area: Rect) {
+    // Probes of the highlighted host
+    let mode_text = app
+        .hosts
+        .get(app.selected_index)
+        .map(|h| {
+            h.probes
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
     let mut spans = vec![
         Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": quit | "),
@@ -189,6 +980,8 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
         Span::raw(": nav | "),
         Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": toggle | "),
+        Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": detail | "),
         Span::styled("a", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": all | "),
         Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
@@ -197,22 +990,109 @@ fn render_help(app: &App, frame: &mut Frame, area: Rect) {
         Span::raw(": pause | "),
         Span::styled("s", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": export | "),
+        Span::styled("c", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": codes | "),
+        Span::styled("t", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": timings | "),
+        Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": percentiles | "),
+        Span::styled("e", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": events | "),
+        Span::styled("l", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": log | "),
     ];
 
+    // First, so a narrow terminal can't cut it off
+    let errors = app.messages.unread_errors();
+    if errors > 0 {
+        spans.insert(0, Span::raw(" | "));
+        spans.insert(
+            0,
+            Span::styled(
+                format!("⚠ {} error{} (l)", errors, if errors == 1 { "" } else { "s" }),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
     if app.paused {
         spans.push(Span::styled("⏸ PAUSED", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
         spans.push(Span::raw(" | "));
     }
 
-    spans.push(Span::raw("Mode: "));
+    spans.push(Span::raw("Probes: "));
     spans.push(Span::styled(&mode_text, Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan)));
 
+    // Probe settings for the highlighted host (marked * when overridden)
+    if let Some(host) = app.hosts.get(app.selected_index) {
+        if let Some(probe) = host.probes.first() {
+            spans.push(Span::raw(" | "));
+            spans.push(Span::styled(
+                format_probe_config(app, &host.ip, *probe),
+                Style::default().fg(Color::Cyan),
+            ));
+        }
+    }
+
     let help_text = Line::from(spans);
     let paragraph = Paragraph::new(help_text).block(Block::default().borders(Borders::ALL));
 
     frame.render_widget(paragraph, area);
 }
 
+fn format_probe_config(app: &App, ip: &IpAddr, probe: Probe) -> String {
+    let config = app.probe_config_for(ip, probe);
+    let marker = if app.has_override(ip) { "*" } else { "" };
+
+    let mut text = format!(
+        "{}every {}ms, timeout {}ms",
+        marker,
+        config.interval.as_millis(),
+        config.timeout.as_millis()
+    );
+    match probe {
+        Probe::Icmp => {
+            text.push_str(&format!(", {}B", config.payload_size));
+            if let Some(ttl) = config.ttl {
+                text.push_str(&format!(", TTL {}", ttl));
+            }
+        }
+        Probe::Http(_) | Probe::Https(_) => {
+            text.push_str(&format!(", {} {}", config.method.as_str(), config.path));
+        }
+        Probe::Tcp(_) => {}
+    }
+    text
+}
+
+fn ping_status_style(status: &PingStatus) -> Style {
+    match status {
+        PingStatus::Active => Style::default().fg(Color::Green),
+        PingStatus::Timeout => Style::default().fg(Color::Red),
+        PingStatus::Unreachable => Style::default().fg(Color::Yellow),
+        PingStatus::NotStarted => Style::default().fg(Color::Gray),
+    }
+}
+
+fn http_status_style(status: &HttpStatus) -> Style {
+    match status {
+        HttpStatus::Success => Style::default().fg(Color::Green),
+        HttpStatus::ClientError => Style::default().fg(Color::Yellow),
+        HttpStatus::ServerError | HttpStatus::NetworkError => Style::default().fg(Color::Red),
+        HttpStatus::AssertionFailed => Style::default().fg(Color::Magenta),
+        HttpStatus::NotStarted => Style::default().fg(Color::Gray),
+    }
+}
+
+fn tcp_status_style(status: &TcpStatus) -> Style {
+    match status {
+        TcpStatus::Open => Style::default().fg(Color::Green),
+        TcpStatus::Refused => Style::default().fg(Color::Yellow),
+        TcpStatus::Filtered | TcpStatus::Error => Style::default().fg(Color::Red),
+        TcpStatus::NotStarted => Style::default().fg(Color::Gray),
+    }
+}
+
 fn format_ping_status(status: &PingStatus) -> String {
     match status {
         PingStatus::NotStarted => "Not Started".to_string(),
@@ -241,6 +1121,140 @@ fn format_http_status(code: Option<u16>, status: &HttpStatus) -> String {
     }
 }
 
+/// Host address with the probe's port, if it has one
+fn format_target(ip: IpAddr, probe: Probe) -> String {
+    match probe {
+        Probe::Icmp => ip.to_string(),
+        Probe::Http(port) | Probe::Tcp(port) => SocketAddr::new(ip, port).to_string(),
+        Probe::Https(port) => format!("https://{}", SocketAddr::new(ip, port)),
+    }
+}
+
+fn format_tcp_status(status: &TcpStatus) -> String {
+    match status {
+        TcpStatus::NotStarted => "Not Started".to_string(),
+        TcpStatus::Open => "Open".to_string(),
+        TcpStatus::Refused => "Refused".to_string(),
+        TcpStatus::Filtered => "Filtered".to_string(),
+        TcpStatus::Error => "Error".to_string(),
+    }
+}
+
+/// One status change: time, probe (and host), old → new state, and how long the old state lasted
+fn format_event(app: &App, event: &HostEvent, with_host: bool) -> Line<'static> {
+    let change = &event.change;
+    let source = if with_host {
+        match app.hostname(&event.ip) {
+            Some(name) => format!("{} ({}) {}", event.ip, name, event.probe),
+            None => format!("{} {}", event.ip, event.probe),
+        }
+    } else {
+        event.probe.to_string()
+    };
+
+    let mut spans = vec![
+        Span::styled(change.at.format("%H:%M:%S ").to_string(), Style::default().fg(Color::Gray)),
+        Span::raw(format!("{} {} → ", source, change.from)),
+        Span::styled(
+            change.to.clone(),
+            event_style(&change.to).add_modifier(Modifier::BOLD),
+        ),
+    ];
+    if let Some(duration) = change.duration {
+        spans.push(Span::styled(
+            format!(" after {}", format_elapsed(duration)),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    Line::from(spans)
+}
+
+/// Color of a status name as recorded in the event log
+fn event_style(status: &str) -> Style {
+    match status {
+        "Active" | "Success" | "Open" => Style::default().fg(Color::Green),
+        "ClientError" | "Refused" | "Unreachable" => Style::default().fg(Color::Yellow),
+        "AssertionFailed" => Style::default().fg(Color::Magenta),
+        "NotStarted" => Style::default().fg(Color::Gray),
+        _ => Style::default().fg(Color::Red),
+    }
+}
+
+/// Coarse human duration, e.g. "4.5s", "12m 03s", "2h 05m"
+fn format_elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+/// Content change count with the time of the latest change, highlighted once it has changed
+fn format_changes_cell(changes: u64, last_change: Option<DateTime<Local>>) -> Cell<'static> {
+    match last_change {
+        Some(at) => Cell::from(format!("{}x {}", changes, at.format("%H:%M:%S")))
+            .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+        None => Cell::from("-"),
+    }
+}
+
+/// Certificates expiring within this many days are highlighted
+const CERT_WARN_DAYS: i64 = 30;
+
+/// Days until certificate expiry, yellow when close and red once expired
+fn format_cert_cell(cert: Option<&CertInfo>) -> Cell<'static> {
+    match cert {
+        Some(cert) => {
+            let days = cert.days_until_expiry();
+            let style = if days < 0 {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else if days <= CERT_WARN_DAYS {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Cell::from(format!("{}d", days)).style(style)
+        }
+        None => Cell::from("-"),
+    }
+}
+
+/// Column headers for the optional latency spread columns
+const SPREAD_HEADERS: [&str; 6] = ["P50", "P90", "P95", "P99", "StdDev", "Jitter"];
+
+fn format_spread(spread: Option<&LatencySpread>) -> Vec<String> {
+    match spread {
+        Some(s) => [s.p50, s.p90, s.p95, s.p99, s.std_dev, s.jitter]
+            .into_iter()
+            .map(|d| format_duration(Some(d)))
+            .collect(),
+        None => vec!["-".to_string(); SPREAD_HEADERS.len()],
+    }
+}
+
+/// Share of failed checks, 0 before the first one
+fn failure_percent(sent: u64, success_rate_percent: f64) -> f64 {
+    if sent == 0 {
+        0.0
+    } else {
+        100.0 - success_rate_percent
+    }
+}
+
+/// Whole seconds as a short axis label, e.g. "30s", "5m", "12h"
+fn format_seconds(secs: f64) -> String {
+    if secs >= 3600.0 {
+        format!("{}h", (secs / 3600.0).round())
+    } else if secs >= 60.0 {
+        format!("{}m", (secs / 60.0).round())
+    } else {
+        format!("{}s", secs.round())
+    }
+}
+
 fn format_duration(duration: Option<Duration>) -> String {
     match duration {
         Some(d) => format!("{:.1}ms", d.as_secs_f64() * 1000.0),
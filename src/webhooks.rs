@@ -0,0 +1,450 @@
+use crate::alerts::{AlertEvent, AlertKind};
+use crate::config::{AlertRule, Webhook, WebhookFormat};
+use crate::messages::MessageSender;
+use crate::stats::Probe;
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Attempts per event, the first included
+const MAX_ATTEMPTS: u32 = 4;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fans alert events out to one delivery task per webhook, so a slow or
+/// failing endpoint never holds up the others or the UI
+pub struct WebhookSender {
+    senders: Vec<mpsc::UnboundedSender<AlertEvent>>,
+}
+
+impl WebhookSender {
+    /// Start a delivery task for each webhook; failed deliveries are reported to `messages`
+    pub fn spawn(webhooks: Vec<Webhook>, rate_per_minute: u32, messages: MessageSender) -> Self {
+        let senders = webhooks
+            .into_iter()
+            .map(|webhook| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                tokio::spawn(deliver_events(webhook, rate_per_minute, rx, messages.clone()));
+                tx
+            })
+            .collect();
+        Self { senders }
+    }
+
+    pub fn send(&self, event: &AlertEvent) {
+        for sender in &self.senders {
+            let _ = sender.send(event.clone());
+        }
+    }
+}
+
+/// Post each event in turn, dropping (and counting) alerts over the rate limit.
+///
+/// Recoveries are always delivered, so a flapping host never ends on a dropped "recovered".
+async fn deliver_events(
+    webhook: Webhook,
+    rate_per_minute: u32,
+    mut events: mpsc::UnboundedReceiver<AlertEvent>,
+    messages: MessageSender,
+) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            messages.error(format!("Failed to create webhook client: {}", e));
+            return;
+        }
+    };
+    let mut sent: VecDeque<Instant> = VecDeque::new();
+    let mut suppressed = 0u32;
+
+    while let Some(event) = events.recv().await {
+        while sent.front().is_some_and(|t| t.elapsed() >= webhook.rate_window) {
+            sent.pop_front();
+        }
+        if sent.len() >= rate_per_minute as usize && event.kind == AlertKind::Firing {
+            suppressed += 1;
+            continue;
+        }
+        sent.push_back(Instant::now());
+
+        let body = render_body(&webhook.format, &event, suppressed);
+        suppressed = 0;
+        if let Err(e) = post_with_retry(&client, &webhook, &body).await {
+            // The URL itself may carry a secret token, so name only its host
+            let host = reqwest::Url::parse(&webhook.url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_default();
+            messages.warning(format!("Webhook delivery to {} failed: {}", host, e));
+        }
+    }
+}
+
+/// POST the body, retrying network errors, 429 and 5xx with exponential backoff.
+///
+/// Returns why the last attempt failed if none succeeded.
+async fn post_with_retry(client: &reqwest::Client, webhook: &Webhook, body: &Value) -> Result<(), String> {
+    let mut backoff = webhook.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        let (retry, failure) = match client.post(&webhook.url).json(body).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                (
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+                    format!("HTTP {}", status.as_u16()),
+                )
+            }
+            Err(e) => (true, e.without_url().to_string()),
+        };
+        if !retry || attempt == MAX_ATTEMPTS {
+            return Err(format!("{} (attempt {} of {})", failure, attempt, MAX_ATTEMPTS));
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// Request body for an event; `suppressed` counts events dropped by the rate limit since the last delivery
+fn render_body(format: &WebhookFormat, event: &AlertEvent, suppressed: u32) -> Value {
+    match format {
+        WebhookFormat::Generic => {
+            let mut body = event.to_json();
+            body["suppressed"] = json!(suppressed);
+            body
+        }
+        WebhookFormat::Slack => {
+            let host = match &event.hostname {
+                Some(name) => format!("{} ({})", event.ip, name),
+                None => event.ip.to_string(),
+            };
+            let mut text = match event.kind {
+                AlertKind::Firing => format!(
+                    ":red_circle: *ALERT* {} {}: {} `{}`",
+                    host, event.probe, event.message, event.rule
+                ),
+                AlertKind::Resolved => format!(
+                    ":large_green_circle: *RECOVERED* {} {}: `{}` cleared after {}s",
+                    host,
+                    event.probe,
+                    event.rule,
+                    event.duration.map(|d| d.as_secs()).unwrap_or(0)
+                ),
+            };
+            if suppressed > 0 {
+                text.push_str(&format!("\n_{} earlier alert(s) suppressed by rate limit_", suppressed));
+            }
+            json!({ "text": text })
+        }
+        // Templates are checked up front and values are escaped, so this stays valid JSON
+        WebhookFormat::Template(template) => {
+            let body = render_template(template, event, suppressed);
+            serde_json::from_str(&body).unwrap_or(Value::String(body))
+        }
+    }
+}
+
+/// Fill in `{state}`, `{host}`, ... (the `PINGER_ALERT_*` fields, lowercased) and
+/// `{suppressed}`. Values are JSON-escaped without quotes, so they belong inside strings.
+///
+/// One pass over the template, so placeholder text inside a value is left as is.
+fn render_template(template: &str, event: &AlertEvent, suppressed: u32) -> String {
+    let fields: Vec<(String, String)> = event
+        .fields()
+        .into_iter()
+        .map(|(name, value)| (name.to_lowercase(), value))
+        .chain([("suppressed".to_string(), suppressed.to_string())])
+        .collect();
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        body.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let field = rest[1..]
+            .find(['{', '}'])
+            .filter(|end| rest.as_bytes()[end + 1] == b'}')
+            .and_then(|end| fields.iter().find(|(name, _)| *name == rest[1..end + 1]));
+        match field {
+            Some((name, value)) => {
+                let escaped = Value::String(value.clone()).to_string();
+                body.push_str(&escaped[1..escaped.len() - 1]);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                body.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+/// Make sure a `--webhook-template` renders to valid JSON for both alerts and recoveries
+pub fn check_template(template: &str) -> Result<()> {
+    let firing = AlertEvent {
+        kind: AlertKind::Firing,
+        ip: [192, 0, 2, 1].into(),
+        hostname: Some("example \"host\"".to_string()),
+        probe: Probe::Icmp,
+        rule: AlertRule::Unreachable(5),
+        message: "5 failed probes in a row".to_string(),
+        at: Local::now(),
+        duration: None,
+    };
+    let resolved = AlertEvent {
+        kind: AlertKind::Resolved,
+        duration: Some(Duration::from_secs(60)),
+        ..firing.clone()
+    };
+    for event in [firing, resolved] {
+        let body = render_template(template, &event, 0);
+        if let Err(e) = serde_json::from_str::<Value>(&body) {
+            return Err(anyhow!("Webhook template is not valid JSON once filled in ({}): {}", e, body));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Messages;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::{Bytes, Incoming};
+    use hyper::service::service_fn;
+    use hyper::{Request, Response};
+    use hyper_util::rt::TokioIo;
+    use parking_lot::Mutex;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Short enough that the rate limit test can wait it out
+    const RATE_WINDOW: Duration = Duration::from_millis(500);
+
+    /// Loopback webhook receiver answering with scripted status codes, then 200
+    struct StandIn {
+        addr: SocketAddr,
+        /// Path and JSON body of every request, in arrival order
+        requests: Arc<Mutex<Vec<(String, Value)>>>,
+    }
+
+    impl StandIn {
+        async fn start(statuses: &[u16]) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let statuses = Arc::new(Mutex::new(VecDeque::from(statuses.to_vec())));
+
+            let recorded = Arc::clone(&requests);
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let recorded = Arc::clone(&recorded);
+                    let statuses = Arc::clone(&statuses);
+                    let service = service_fn(move |request: Request<Incoming>| {
+                        let recorded = Arc::clone(&recorded);
+                        let status = statuses.lock().pop_front().unwrap_or(200);
+                        async move {
+                            let path = request.uri().path().to_string();
+                            let body = request.into_body().collect().await.unwrap().to_bytes();
+                            let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                            recorded.lock().push((path, body));
+                            let mut response = Response::new(Full::new(Bytes::new()));
+                            *response.status_mut() = StatusCode::from_u16(status).unwrap();
+                            Ok::<_, Infallible>(response)
+                        }
+                    });
+                    tokio::spawn(
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service),
+                    );
+                }
+            });
+            Self { addr, requests }
+        }
+
+        fn webhook(&self, format: WebhookFormat, path: &str) -> Webhook {
+            Webhook {
+                url: format!("http://{}{}", self.addr, path),
+                format,
+                initial_backoff: Duration::from_millis(10),
+                rate_window: RATE_WINDOW,
+            }
+        }
+
+        /// Requests received so far, after waiting up to 5s for `count` of them
+        async fn wait_for(&self, count: usize) -> Vec<(String, Value)> {
+            for _ in 0..500 {
+                if self.requests.lock().len() >= count {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            self.requests.lock().clone()
+        }
+    }
+
+    fn event(kind: AlertKind) -> AlertEvent {
+        AlertEvent {
+            kind,
+            ip: [127, 0, 0, 1].into(),
+            hostname: Some("router".to_string()),
+            probe: Probe::Http(80),
+            rule: AlertRule::Unreachable(3),
+            message: "3 failed probes in a row".to_string(),
+            at: Local::now(),
+            duration: (kind == AlertKind::Resolved).then_some(Duration::from_secs(42)),
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_generic_slack_and_templated_bodies() {
+        let server = StandIn::start(&[]).await;
+        let messages = Messages::default();
+        let template = r#"{"summary": "{host} ({hostname}) {state}: {message}", "after": "{duration}"}"#;
+        let sender = WebhookSender::spawn(
+            vec![
+                server.webhook(WebhookFormat::Generic, "/generic"),
+                server.webhook(WebhookFormat::Slack, "/slack"),
+                server.webhook(WebhookFormat::Template(template.to_string()), "/template"),
+            ],
+            20,
+            messages.sender(),
+        );
+        sender.send(&event(AlertKind::Resolved));
+
+        let mut requests = server.wait_for(3).await;
+        requests.sort_by(|a, b| a.0.cmp(&b.0));
+        let paths: Vec<&str> = requests.iter().map(|(path, _)| path.as_str()).collect();
+        let bodies: Vec<&Value> = requests.iter().map(|(_, body)| body).collect();
+        assert_eq!(paths, ["/generic", "/slack", "/template"]);
+
+        assert_eq!(bodies[0]["state"], "resolved");
+        assert_eq!(bodies[0]["host"], "127.0.0.1");
+        assert_eq!(bodies[0]["hostname"], "router");
+        assert_eq!(bodies[0]["probe"], "HTTP:80");
+        assert_eq!(bodies[0]["rule"], "unreachable=3");
+        assert_eq!(bodies[0]["duration_secs"], 42);
+        assert_eq!(bodies[0]["suppressed"], 0);
+
+        assert_eq!(bodies[1].as_object().map(|o| o.len()), Some(1));
+        assert_eq!(
+            bodies[1]["text"],
+            ":large_green_circle: *RECOVERED* 127.0.0.1 (router) HTTP:80: `unreachable=3` cleared after 42s"
+        );
+
+        assert_eq!(
+            *bodies[2],
+            json!({"summary": "127.0.0.1 (router) resolved: 3 failed probes in a row", "after": "42"})
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_and_429_but_not_other_failures() {
+        let server = StandIn::start(&[503, 429, 200, 400]).await;
+        let mut messages = Messages::default();
+        let sender = WebhookSender::spawn(
+            vec![server.webhook(WebhookFormat::Generic, "/")],
+            20,
+            messages.sender(),
+        );
+
+        // Delivered on the third attempt
+        sender.send(&event(AlertKind::Firing));
+        assert_eq!(server.wait_for(3).await.len(), 3);
+        // A 400 is final
+        sender.send(&event(AlertKind::Firing));
+        assert_eq!(server.wait_for(4).await.len(), 4);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.requests.lock().len(), 4);
+
+        messages.receive();
+        let warnings: Vec<String> = messages.entries().map(|m| m.text.clone()).collect();
+        assert_eq!(warnings, ["Webhook delivery to 127.0.0.1 failed: HTTP 400 (attempt 1 of 4)"]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let server = StandIn::start(&[500; MAX_ATTEMPTS as usize + 1]).await;
+        let mut messages = Messages::default();
+        let sender = WebhookSender::spawn(
+            vec![server.webhook(WebhookFormat::Generic, "/")],
+            20,
+            messages.sender(),
+        );
+        sender.send(&event(AlertKind::Firing));
+        assert_eq!(server.wait_for(MAX_ATTEMPTS as usize).await.len(), MAX_ATTEMPTS as usize);
+
+        for _ in 0..100 {
+            messages.receive();
+            if messages.entries().len() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let warnings: Vec<String> = messages.entries().map(|m| m.text.clone()).collect();
+        assert_eq!(warnings, ["Webhook delivery to 127.0.0.1 failed: HTTP 500 (attempt 4 of 4)"]);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_drops_alerts_but_not_recoveries() {
+        let server = StandIn::start(&[]).await;
+        let messages = Messages::default();
+        let sender = WebhookSender::spawn(
+            vec![server.webhook(WebhookFormat::Generic, "/")],
+            2,
+            messages.sender(),
+        );
+        for _ in 0..5 {
+            sender.send(&event(AlertKind::Firing));
+        }
+        assert_eq!(server.wait_for(2).await.len(), 2);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.requests.lock().len(), 2);
+
+        // Over the limit, a recovery still goes out and reports what was dropped
+        sender.send(&event(AlertKind::Resolved));
+        sender.send(&event(AlertKind::Firing));
+        let requests = server.wait_for(3).await;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].1["state"], "resolved");
+        assert_eq!(requests[2].1["suppressed"], 3);
+
+        // Once the window has passed, the next alert carries the newer count
+        tokio::time::sleep(RATE_WINDOW).await;
+        sender.send(&event(AlertKind::Firing));
+        let requests = server.wait_for(4).await;
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[3].1["state"], "firing");
+        assert_eq!(requests[3].1["suppressed"], 1);
+    }
+
+    #[test]
+    fn templates_fill_placeholders_in_one_pass() {
+        let mut firing = event(AlertKind::Firing);
+        firing.message = "GET {host} failed: {message}".to_string();
+        let body = render_template(r#"{"text": "{host}: {message} {unknown} {{state}}", "n": {suppressed}}"#, &firing, 2);
+        assert_eq!(
+            body,
+            r#"{"text": "127.0.0.1: GET {host} failed: {message} {unknown} {firing}", "n": 2}"#
+        );
+    }
+
+    #[test]
+    fn templates_must_render_to_json() {
+        assert!(check_template(r#"{"text": "{host} is {state} after {duration}s"}"#).is_ok());
+        // Values are escaped, so quotes in them can't break out of the string
+        assert!(check_template(r#"{"text": "{hostname}"}"#).is_ok());
+        // An empty duration leaves a hole outside of a string
+        assert!(check_template(r#"{"after": {duration}}"#).is_err());
+        assert!(check_template("{host} down").is_err());
+    }
+}